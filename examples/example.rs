@@ -28,9 +28,9 @@ fn main() {
                 .and_then(|file| {
                     file.sync_all().and_then(|file| {
                         file.sync_data().and_then(|file| {
-                            file.try_clone().and_then(|(file, _file2)| {
-                                file.metadata().and_then(|(file, metadata)| {
-                                    let mut permissions = metadata.permissions();
+                            AsyncFile::try_clone(file).and_then(|(file, _file2)| {
+                                AsyncFile::metadata(file).and_then(|(file, metadata)| {
+                                    let mut permissions = metadata.permissions().unwrap();
                                     permissions.set_readonly(true);
 
                                     file.set_permissions(permissions)
@@ -0,0 +1,290 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs,
+    io::Error,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use futures::{Async, Future, Poll};
+use tokio_fs::{self, file::OpenFuture, File, OpenOptions, RenameFuture};
+
+use span::OpSpan;
+use {AsyncFile, Inner, SyncPolicy, SyncWith, WriteAll};
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Replace the file at `path` with `contents` so that, even across a crash, it holds either its
+/// old contents or the new ones, never a mix.
+///
+/// This writes to a temporary file next to `path`, flushes it according to `policy`, renames it
+/// over `path`, and then flushes the parent directory so the rename itself is durable. The
+/// temporary file is removed if anything fails along the way. With `SyncPolicy::None` nothing
+/// is flushed, so the replacement is atomic for readers but not across a crash.
+pub fn atomic_write<P, C>(path: P, contents: C, policy: SyncPolicy) -> AtomicWrite<C>
+where
+    P: AsRef<Path>,
+    C: AsRef<[u8]>,
+{
+    let span = OpSpan::new("atomic_write", format_args!("{} bytes", contents.as_ref().len()))
+        .with_path(path.as_ref());
+
+    AtomicWrite {
+        contents: Some(contents),
+        state: AtomicWriteState::Create(AtomicFile::create(path, policy)),
+        span,
+    }
+}
+
+/// A temporary file that replaces its target when committed.
+///
+/// Writes through its `AsyncFile` impl go to the temporary file. `commit` flushes it, renames it
+/// over the target and flushes the target's directory. Dropping an `AtomicFile` without
+/// committing it removes the temporary file, leaving the target untouched.
+pub struct AtomicFile {
+    target: PathBuf,
+    temp: PathBuf,
+    policy: SyncPolicy,
+    committed: bool,
+    file: Option<File>,
+}
+
+impl AtomicFile {
+    /// Create a temporary file next to `path`, to replace `path` once committed.
+    ///
+    /// `policy` controls how the file is flushed on commit, as described on `atomic_write`.
+    pub fn create<P>(path: P, policy: SyncPolicy) -> CreateAtomicFile
+    where
+        P: AsRef<Path>,
+    {
+        let target = path.as_ref().to_path_buf();
+        let temp = temp_path(&target);
+        let open = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(temp.clone());
+
+        CreateAtomicFile {
+            target: Some(target),
+            temp: Some(temp),
+            policy: Some(policy),
+            open,
+        }
+    }
+
+    /// The path this file will replace.
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Replace the target with everything written so far.
+    pub fn commit(mut self) -> Commit {
+        let file = self.file.take().unwrap();
+        let policy = match self.policy {
+            SyncPolicy::DataThenDirectory(_) => SyncPolicy::Data,
+            ref policy => policy.clone(),
+        };
+
+        Commit {
+            state: CommitState::Sync(file.sync_with(policy)),
+            atomic: self,
+        }
+    }
+
+    fn parent(&self) -> PathBuf {
+        match self.target.parent() {
+            Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
+    }
+}
+
+impl Inner for AtomicFile {
+    type File = File;
+
+    fn inner_ref(&self) -> &File {
+        self.file.as_ref().unwrap()
+    }
+
+    fn inner_mut(&mut self) -> &mut File {
+        self.file.as_mut().unwrap()
+    }
+}
+
+// Named after the temporary file, which is where its operations go.
+delegate_async_file!(impl AtomicFile => File, path: temp);
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// A hidden name in the same directory as `target`, so renaming it over `target` never crosses
+/// filesystems.
+fn temp_path(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    target.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        name,
+        process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// The future returned by `AtomicFile::create`.
+pub struct CreateAtomicFile {
+    target: Option<PathBuf>,
+    temp: Option<PathBuf>,
+    policy: Option<SyncPolicy>,
+    open: OpenFuture<PathBuf>,
+}
+
+impl Future for CreateAtomicFile {
+    type Item = AtomicFile;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let file = try_ready!(self.open.poll());
+
+        Ok(Async::Ready(AtomicFile {
+            target: self.target.take().unwrap(),
+            temp: self.temp.take().unwrap(),
+            policy: self.policy.take().unwrap(),
+            committed: false,
+            file: Some(file),
+        }))
+    }
+}
+
+enum CommitState {
+    Sync(SyncWith<File>),
+    Rename(RenameFuture<PathBuf, PathBuf>),
+    OpenDirectory(OpenFuture<PathBuf>),
+    SyncDirectory(File),
+}
+
+/// The future returned by `AtomicFile::commit`.
+///
+/// If it fails or is dropped before the rename happens, the temporary file is removed.
+pub struct Commit {
+    atomic: AtomicFile,
+    state: CommitState,
+}
+
+impl Future for Commit {
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let next = match self.state {
+                CommitState::Sync(ref mut sync) => {
+                    // Close the temporary file before renaming it.
+                    drop(try_ready!(sync.poll()));
+
+                    let (temp, target) = (self.atomic.temp.clone(), self.atomic.target.clone());
+
+                    CommitState::Rename(tokio_fs::rename(temp, target))
+                }
+                CommitState::Rename(ref mut rename) => {
+                    try_ready!(rename.poll());
+                    self.atomic.committed = true;
+
+                    if self.atomic.policy == SyncPolicy::None {
+                        return Ok(Async::Ready(()));
+                    }
+
+                    CommitState::OpenDirectory(File::open(self.atomic.parent()))
+                }
+                CommitState::OpenDirectory(ref mut open) => {
+                    CommitState::SyncDirectory(try_ready!(open.poll()))
+                }
+                CommitState::SyncDirectory(ref mut dir) => {
+                    try_ready!(dir.poll_sync_all());
+
+                    return Ok(Async::Ready(()));
+                }
+            };
+
+            self.state = next;
+        }
+    }
+}
+
+enum AtomicWriteState<C> {
+    Create(CreateAtomicFile),
+    Write(WriteAll<AtomicFile, C>),
+    Commit(Commit),
+}
+
+/// The future returned by `atomic_write`.
+pub struct AtomicWrite<C> {
+    contents: Option<C>,
+    state: AtomicWriteState<C>,
+    span: OpSpan,
+}
+
+impl<C> AtomicWrite<C>
+where
+    C: AsRef<[u8]>,
+{
+    fn poll_steps(&mut self) -> Poll<(), Error> {
+        loop {
+            let next = match self.state {
+                AtomicWriteState::Create(ref mut create) => {
+                    let file = try_ready!(create.poll());
+
+                    AtomicWriteState::Write(file.write_all(self.contents.take().unwrap()))
+                }
+                AtomicWriteState::Write(ref mut write) => {
+                    let (file, _, _) = try_ready!(write.poll());
+
+                    AtomicWriteState::Commit(file.commit())
+                }
+                AtomicWriteState::Commit(ref mut commit) => return commit.poll(),
+            };
+
+            self.state = next;
+        }
+    }
+}
+
+impl<C> Future for AtomicWrite<C>
+where
+    C: AsRef<[u8]>,
+{
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let span = self.span.open(None);
+
+        span.poll(|| self.poll_steps())
+    }
+}
@@ -0,0 +1,71 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{fs::File as StdFile, io::{Error, Result}};
+
+use futures::{Async, Poll};
+use tokio_fs::File;
+
+/// Run `f` on tokio's blocking pool against the `std::fs::File` underneath a `tokio_fs::File`.
+///
+/// tokio-fs doesn't lend out the file it wraps, so this works on a duplicate of its descriptor.
+/// The duplicate shares the original's open file description, so locks and positional I/O
+/// through it behave as they would on the original.
+///
+/// tokio-fs makes the duplicate on the blocking pool, and once a thread there starts blocking it
+/// stays that way until the poll returns. So `f` runs straight after, in the same blocking
+/// section, and the duplicate is only made once there's capacity to use it.
+pub(crate) fn poll_std<F, T>(file: &mut File, f: F) -> Poll<T, Error>
+where
+    F: FnOnce(&StdFile) -> Result<T>,
+{
+    let std = try_ready!(file.poll_try_clone()).into_std();
+
+    f(&std).map(Async::Ready)
+}
+
+#[cfg(unix)]
+pub(crate) fn read_at(file: &StdFile, buf: &mut [u8], offset: u64) -> Result<usize> {
+    use std::os::unix::fs::FileExt;
+
+    file.read_at(buf, offset)
+}
+
+#[cfg(unix)]
+pub(crate) fn write_at(file: &StdFile, buf: &[u8], offset: u64) -> Result<usize> {
+    use std::os::unix::fs::FileExt;
+
+    file.write_at(buf, offset)
+}
+
+// Windows has no positional I/O that leaves the cursor alone; `seek_read` and `seek_write`
+// are the closest it offers.
+#[cfg(windows)]
+pub(crate) fn read_at(file: &StdFile, buf: &mut [u8], offset: u64) -> Result<usize> {
+    use std::os::windows::fs::FileExt;
+
+    file.seek_read(buf, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn write_at(file: &StdFile, buf: &[u8], offset: u64) -> Result<usize> {
+    use std::os::windows::fs::FileExt;
+
+    file.seek_write(buf, offset)
+}
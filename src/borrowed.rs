@@ -0,0 +1,72 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The futures for operations on a borrowed file.
+//!
+//! `&mut T` is an `AsyncFile` whenever `T` is, so an operation can run on a file that lives
+//! inside something else without moving it out and back. The futures then resolve to the
+//! borrow. The aliases here name those futures, e.g. `borrowed::Seek<'a, T>` for
+//! `Seek<&'a mut T>`.
+//!
+//! ```rust
+//! # extern crate file_futures;
+//! # extern crate futures;
+//! use std::io::SeekFrom;
+//!
+//! use file_futures::{borrowed, AsyncFile, MemFile};
+//! use futures::Future;
+//!
+//! struct Log {
+//!     file: MemFile,
+//! }
+//!
+//! impl Log {
+//!     fn rewind(&mut self) -> borrowed::Seek<'_, MemFile> {
+//!         (&mut self.file).seek(SeekFrom::Start(0))
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let mut log = Log { file: MemFile::with_contents("entry") };
+//! let (_, pos) = log.rewind().wait().unwrap();
+//!
+//! assert_eq!(pos, 0);
+//! assert_eq!(log.file.contents(), b"entry");
+//! # }
+//! ```
+
+pub type Seek<'a, T> = ::Seek<&'a mut T>;
+pub type SyncAll<'a, T> = ::SyncAll<&'a mut T>;
+pub type SyncData<'a, T> = ::SyncData<&'a mut T>;
+pub type SetLen<'a, T> = ::SetLen<&'a mut T>;
+pub type GetMetadata<'a, T> = ::GetMetadata<&'a mut T>;
+pub type TryClone<'a, T> = ::TryClone<&'a mut T>;
+pub type SetPermissions<'a, T> = ::SetPermissions<&'a mut T>;
+pub type SyncWith<'a, T> = ::SyncWith<&'a mut T>;
+pub type Read<'a, T, B> = ::Read<&'a mut T, B>;
+pub type Write<'a, T, B> = ::Write<&'a mut T, B>;
+pub type ReadExact<'a, T, B> = ::ReadExact<&'a mut T, B>;
+pub type WriteAll<'a, T, B> = ::WriteAll<&'a mut T, B>;
+pub type ReadAt<'a, T, B> = ::ReadAt<&'a mut T, B>;
+pub type WriteAt<'a, T, B> = ::WriteAt<&'a mut T, B>;
+pub type ReadToEnd<'a, T> = ::ReadToEnd<&'a mut T>;
+pub type ReadToString<'a, T> = ::ReadToString<&'a mut T>;
+pub type Lock<'a, T> = ::Lock<&'a mut T>;
+pub type Unlock<'a, T> = ::Unlock<&'a mut T>;
+pub type LockedFile<'a, T> = ::LockedFile<&'a mut T>;
@@ -0,0 +1,150 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{fs::Permissions, io::{Error, SeekFrom}};
+
+use futures::{
+    executor::{self, Notify},
+    future, Future, Poll,
+};
+
+use {AsyncFile, FileMetadata, LockMode};
+
+/// How an operation future polls the file it holds.
+///
+/// The futures keep their state machines in a `poll_with` method that takes a driver, so the
+/// same code backs both their futures 0.1 `Future` impls, which use `Direct`, and their
+/// `std::future::Future` impls, which poll through a `Context`.
+pub(crate) trait Driver<T> {
+    type Cloned;
+
+    fn poll_seek(&mut self, file: &mut T, pos: SeekFrom) -> Poll<u64, Error>;
+    fn poll_sync_all(&mut self, file: &mut T) -> Poll<(), Error>;
+    fn poll_sync_data(&mut self, file: &mut T) -> Poll<(), Error>;
+    fn poll_set_len(&mut self, file: &mut T, size: u64) -> Poll<(), Error>;
+    fn poll_metadata(&mut self, file: &mut T) -> Poll<FileMetadata, Error>;
+    fn poll_try_clone(&mut self, file: &mut T) -> Poll<Self::Cloned, Error>;
+    fn poll_set_permissions(&mut self, file: &mut T, perm: Permissions) -> Poll<(), Error>;
+    fn poll_read(&mut self, file: &mut T, buf: &mut [u8]) -> Poll<usize, Error>;
+    fn poll_write(&mut self, file: &mut T, buf: &[u8]) -> Poll<usize, Error>;
+    fn poll_read_at(&mut self, file: &mut T, buf: &mut [u8], offset: u64) -> Poll<usize, Error>;
+    fn poll_write_at(&mut self, file: &mut T, buf: &[u8], offset: u64) -> Poll<usize, Error>;
+    fn poll_lock(&mut self, file: &mut T, mode: LockMode) -> Poll<(), Error>;
+    fn poll_try_lock(&mut self, file: &mut T, mode: LockMode) -> Poll<(), Error>;
+    fn poll_unlock(&mut self, file: &mut T) -> Poll<(), Error>;
+
+    /// Poll a futures 0.1 future that isn't one of the file's own operations, like opening the
+    /// directory `SyncWith` flushes.
+    fn poll_future<F>(&mut self, future: &mut F) -> Poll<F::Item, F::Error>
+    where
+        F: Future;
+
+    /// How a `LockedFile` should release its lock when it's dropped.
+    fn release(&self) -> fn(&mut T);
+}
+
+/// Polls files through `AsyncFile` from inside a futures 0.1 task.
+pub(crate) struct Direct;
+
+impl<T> Driver<T> for Direct
+where
+    T: AsyncFile,
+{
+    type Cloned = T::Cloned;
+
+    fn poll_seek(&mut self, file: &mut T, pos: SeekFrom) -> Poll<u64, Error> {
+        file.poll_seek(pos)
+    }
+
+    fn poll_sync_all(&mut self, file: &mut T) -> Poll<(), Error> {
+        file.poll_sync_all()
+    }
+
+    fn poll_sync_data(&mut self, file: &mut T) -> Poll<(), Error> {
+        file.poll_sync_data()
+    }
+
+    fn poll_set_len(&mut self, file: &mut T, size: u64) -> Poll<(), Error> {
+        file.poll_set_len(size)
+    }
+
+    fn poll_metadata(&mut self, file: &mut T) -> Poll<FileMetadata, Error> {
+        file.poll_metadata()
+    }
+
+    fn poll_try_clone(&mut self, file: &mut T) -> Poll<T::Cloned, Error> {
+        file.poll_try_clone()
+    }
+
+    fn poll_set_permissions(&mut self, file: &mut T, perm: Permissions) -> Poll<(), Error> {
+        file.poll_set_permissions(perm)
+    }
+
+    fn poll_read(&mut self, file: &mut T, buf: &mut [u8]) -> Poll<usize, Error> {
+        file.poll_read(buf)
+    }
+
+    fn poll_write(&mut self, file: &mut T, buf: &[u8]) -> Poll<usize, Error> {
+        file.poll_write(buf)
+    }
+
+    fn poll_read_at(&mut self, file: &mut T, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        file.poll_read_at(buf, offset)
+    }
+
+    fn poll_write_at(&mut self, file: &mut T, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        file.poll_write_at(buf, offset)
+    }
+
+    fn poll_lock(&mut self, file: &mut T, mode: LockMode) -> Poll<(), Error> {
+        file.poll_lock(mode)
+    }
+
+    fn poll_try_lock(&mut self, file: &mut T, mode: LockMode) -> Poll<(), Error> {
+        file.poll_try_lock(mode)
+    }
+
+    fn poll_unlock(&mut self, file: &mut T) -> Poll<(), Error> {
+        file.poll_unlock()
+    }
+
+    fn poll_future<F>(&mut self, future: &mut F) -> Poll<F::Item, F::Error>
+    where
+        F: Future,
+    {
+        future.poll()
+    }
+
+    fn release(&self) -> fn(&mut T) {
+        fn release<T: AsyncFile>(file: &mut T) {
+            // The guard may be dropped outside of any task, so poll the unlock inside one of our
+            // own that nobody waits on.
+            let unlock = future::poll_fn(|| file.poll_unlock());
+            let _ = executor::spawn(unlock).poll_future_notify(&&NoopNotify, 0);
+        }
+
+        release::<T>
+    }
+}
+
+struct NoopNotify;
+
+impl Notify for NoopNotify {
+    fn notify(&self, _id: usize) {}
+}
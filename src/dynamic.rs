@@ -0,0 +1,214 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs::Permissions,
+    io::{Error, SeekFrom},
+    path::Path,
+};
+
+use futures::{Async, Poll};
+
+use {AsyncFile, FileMetadata, LockMode};
+
+/// An object-safe counterpart to `AsyncFile`, for picking a file's type at runtime.
+///
+/// Every `AsyncFile` that can be sent between threads and whose `try_clone` resolves to its own
+/// type implements it, and `Box<dyn DynAsyncFile>` implements `AsyncFile`, so the usual futures
+/// work on a boxed file whatever is behind it. `try_clone` on a boxed file resolves to another
+/// boxed file.
+///
+/// ```rust
+/// # extern crate file_futures;
+/// # extern crate futures;
+/// use std::io::SeekFrom;
+///
+/// use file_futures::{AsyncFile, DynAsyncFile, MemFile, PooledFile};
+/// use futures::Future;
+///
+/// fn open(in_memory: bool) -> Box<dyn DynAsyncFile> {
+///     if in_memory {
+///         Box::new(MemFile::with_contents("hello"))
+///     } else {
+///         Box::new(PooledFile::new(std::fs::File::open("Cargo.toml").unwrap()))
+///     }
+/// }
+///
+/// # fn main() {
+/// let (_, n) = open(true).seek(SeekFrom::End(0)).wait().unwrap();
+/// assert_eq!(n, 5);
+/// # }
+/// ```
+pub trait DynAsyncFile: Send {
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error>;
+    fn poll_sync_all(&mut self) -> Poll<(), Error>;
+    fn poll_sync_data(&mut self) -> Poll<(), Error>;
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error>;
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error>;
+    fn poll_try_clone(&mut self) -> Poll<Box<dyn DynAsyncFile>, Error>;
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error>;
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error>;
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error>;
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error>;
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error>;
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error>;
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error>;
+    fn poll_unlock(&mut self) -> Poll<(), Error>;
+    fn path(&self) -> Option<&Path>;
+    fn start_operation(&mut self);
+}
+
+impl<T> DynAsyncFile for T
+where
+    T: AsyncFile<Cloned = T> + Send + 'static,
+{
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        AsyncFile::poll_seek(self, pos)
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        AsyncFile::poll_sync_all(self)
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        AsyncFile::poll_sync_data(self)
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        AsyncFile::poll_set_len(self, size)
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        AsyncFile::poll_metadata(self)
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<Box<dyn DynAsyncFile>, Error> {
+        let cloned = try_ready!(AsyncFile::poll_try_clone(self));
+
+        Ok(Async::Ready(Box::new(cloned)))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        AsyncFile::poll_set_permissions(self, perm)
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        AsyncFile::poll_read(self, buf)
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        AsyncFile::poll_write(self, buf)
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        AsyncFile::poll_read_at(self, buf, offset)
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        AsyncFile::poll_write_at(self, buf, offset)
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        AsyncFile::poll_lock(self, mode)
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        AsyncFile::poll_try_lock(self, mode)
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        AsyncFile::poll_unlock(self)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        AsyncFile::path(self)
+    }
+
+    fn start_operation(&mut self) {
+        AsyncFile::start_operation(self)
+    }
+}
+
+impl AsyncFile for Box<dyn DynAsyncFile> {
+    type Cloned = Box<dyn DynAsyncFile>;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        DynAsyncFile::poll_seek(&mut **self, pos)
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        DynAsyncFile::poll_sync_all(&mut **self)
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        DynAsyncFile::poll_sync_data(&mut **self)
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        DynAsyncFile::poll_set_len(&mut **self, size)
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        DynAsyncFile::poll_metadata(&mut **self)
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<Self::Cloned, Error> {
+        DynAsyncFile::poll_try_clone(&mut **self)
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        DynAsyncFile::poll_set_permissions(&mut **self, perm)
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        DynAsyncFile::poll_read(&mut **self, buf)
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        DynAsyncFile::poll_write(&mut **self, buf)
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        DynAsyncFile::poll_read_at(&mut **self, buf, offset)
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        DynAsyncFile::poll_write_at(&mut **self, buf, offset)
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        DynAsyncFile::poll_lock(&mut **self, mode)
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        DynAsyncFile::poll_try_lock(&mut **self, mode)
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        DynAsyncFile::poll_unlock(&mut **self)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        DynAsyncFile::path(&**self)
+    }
+
+    fn start_operation(&mut self) {
+        DynAsyncFile::start_operation(&mut **self)
+    }
+}
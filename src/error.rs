@@ -0,0 +1,140 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{error, fmt, io::{Error, ErrorKind}};
+
+/// The error produced by the futures in this crate.
+///
+/// Alongside the `io::Error`, it carries the file the operation was running on, so a failed
+/// operation doesn't cost the caller their handle, and the name of the operation that failed.
+pub struct FileError<T> {
+    error: Error,
+    file: T,
+    operation: &'static str,
+}
+
+impl<T> FileError<T> {
+    pub(crate) fn new(operation: &'static str, error: Error, file: T) -> Self {
+        FileError {
+            error,
+            file,
+            operation,
+        }
+    }
+
+    /// The name of the operation that failed, e.g. `"set_len"`.
+    pub fn operation(&self) -> &'static str {
+        self.operation
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.error.kind()
+    }
+
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    pub fn file(&self) -> &T {
+        &self.file
+    }
+
+    pub fn file_mut(&mut self) -> &mut T {
+        &mut self.file
+    }
+
+    /// Recover the file, discarding the error.
+    pub fn into_file(self) -> T {
+        self.file
+    }
+
+    pub fn into_parts(self) -> (T, Error) {
+        (self.file, self.error)
+    }
+}
+
+impl<T> fmt::Debug for FileError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FileError")
+            .field("operation", &self.operation)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for FileError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.operation, self.error)
+    }
+}
+
+impl<T> error::Error for FileError<T> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<T> From<FileError<T>> for Error {
+    fn from(e: FileError<T>) -> Self {
+        e.error
+    }
+}
+
+/// The error inside the `io::Error` that `read_to_end` and `read_to_string` fail with when more
+/// bytes are left in a file than their limit.
+///
+/// The `io::Error` has kind `InvalidData`; downcast its inner error to tell this case apart:
+///
+/// ```rust
+/// # use std::io::{Error, ErrorKind};
+/// use file_futures::LimitExceeded;
+///
+/// fn is_too_large(e: &Error) -> bool {
+///     e.get_ref().map_or(false, |inner| inner.is::<LimitExceeded>())
+/// }
+/// # assert!(!is_too_large(&Error::new(ErrorKind::InvalidData, "nope")));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LimitExceeded {
+    limit: u64,
+}
+
+impl LimitExceeded {
+    pub(crate) fn new(limit: u64) -> Self {
+        LimitExceeded { limit }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "file has more than the {} byte limit left to read", self.limit)
+    }
+}
+
+impl error::Error for LimitExceeded {}
+
+impl From<LimitExceeded> for Error {
+    fn from(e: LimitExceeded) -> Self {
+        Error::new(ErrorKind::InvalidData, e)
+    }
+}
@@ -0,0 +1,407 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    cmp,
+    collections::HashMap,
+    fs::Permissions,
+    io::{Error, ErrorKind, SeekFrom},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use futures::{task, Async, Poll};
+
+use timer::{self, Wakeup};
+use {AsyncFile, FileMetadata, FileOp, LockMode};
+
+/// Something for a `FaultyFile` to do to an operation instead of, or before, running it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail with `EIO`.
+    Eio,
+    /// Fail with `ENOSPC`.
+    Enospc,
+    /// Fail with the raw OS error `errno`.
+    Os(i32),
+    /// Fail with a plain error of this kind.
+    Error(ErrorKind),
+    /// Write at most this many bytes. Only affects `write` and `write_at`.
+    ShortWrite(usize),
+    /// Report `NotReady` until this much time has passed, then run the operation.
+    Delay(Duration),
+    /// Report `NotReady` once, notifying the task straight away, then run the operation.
+    NotReady,
+}
+
+#[cfg(unix)]
+const EIO: i32 = 5;
+#[cfg(unix)]
+const ENOSPC: i32 = 28;
+
+impl Fault {
+    fn error(&self) -> Option<Error> {
+        match *self {
+            Fault::Eio => Some(eio()),
+            Fault::Enospc => Some(enospc()),
+            Fault::Os(errno) => Some(Error::from_raw_os_error(errno)),
+            Fault::Error(kind) => Some(Error::new(kind, "injected fault")),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn eio() -> Error {
+    Error::from_raw_os_error(EIO)
+}
+
+#[cfg(not(unix))]
+fn eio() -> Error {
+    Error::new(ErrorKind::Other, "injected EIO")
+}
+
+#[cfg(unix)]
+fn enospc() -> Error {
+    Error::from_raw_os_error(ENOSPC)
+}
+
+#[cfg(not(unix))]
+fn enospc() -> Error {
+    Error::new(ErrorKind::StorageFull, "injected ENOSPC")
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Trigger {
+    /// The call with this index, counting from zero.
+    Call(u64),
+    /// Each call, with this probability.
+    Probability(f64),
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    op: FileOp,
+    trigger: Trigger,
+    fault: Fault,
+}
+
+/// Which faults a `FaultyFile` injects, and when.
+///
+/// Rules are checked in the order they were added, and the first one that fires decides the
+/// call's fault. Calls are counted per operation from the first poll of each one, so an
+/// operation that reports `NotReady` is still a single call.
+///
+/// Probabilities are drawn from a generator seeded with `seed`, so a plan run against the same
+/// sequence of calls injects the same faults every time.
+#[derive(Clone, Debug)]
+pub struct FaultPlan {
+    seed: u64,
+    rules: Vec<Rule>,
+}
+
+impl FaultPlan {
+    /// A plan that injects nothing yet.
+    pub fn new(seed: u64) -> Self {
+        FaultPlan {
+            seed,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Inject `fault` into the `call`th call of `op`, counting from zero.
+    pub fn on_call(mut self, op: FileOp, call: u64, fault: Fault) -> Self {
+        self.rules.push(Rule {
+            op,
+            trigger: Trigger::Call(call),
+            fault,
+        });
+        self
+    }
+
+    /// Inject `fault` into each call of `op` with the given probability, between 0 and 1.
+    pub fn with_probability(mut self, op: FileOp, probability: f64, fault: Fault) -> Self {
+        self.rules.push(Rule {
+            op,
+            trigger: Trigger::Probability(probability),
+            fault,
+        });
+        self
+    }
+}
+
+/// The plan as it's being played out, shared with every clone of the file.
+#[derive(Debug)]
+struct State {
+    rules: Vec<Rule>,
+    rng: u64,
+    calls: HashMap<FileOp, u64>,
+}
+
+impl State {
+    fn start(&mut self, op: FileOp) -> Option<Fault> {
+        let call = {
+            let calls = self.calls.entry(op).or_insert(0);
+            *calls += 1;
+            *calls - 1
+        };
+
+        for i in 0..self.rules.len() {
+            let rule = self.rules[i].clone();
+
+            if rule.op != op {
+                continue;
+            }
+
+            let fires = match rule.trigger {
+                Trigger::Call(n) => n == call,
+                Trigger::Probability(p) => self.next_f64() < p,
+            };
+
+            if fires {
+                return Some(rule.fault);
+            }
+        }
+
+        None
+    }
+
+    /// splitmix64, which is plenty for picking faults and needs no dependencies.
+    fn next_u64(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The operation in flight, and what's being done to it.
+#[derive(Debug)]
+struct Current {
+    op: FileOp,
+    fault: Option<Fault>,
+    started: Instant,
+    not_ready: bool,
+    /// Wakes the task once a `Delay` is over.
+    wakeup: Option<Wakeup>,
+}
+
+/// An `AsyncFile` that injects errors, short writes, delays and spurious `NotReady`s into the
+/// file it wraps, as a `FaultPlan` says.
+///
+/// Meant for testing how code copes with a misbehaving disk. Faults that fail an operation do
+/// so without reaching the wrapped file. `try_clone` resolves to a `FaultyFile` playing out the
+/// same plan, with calls through either handle counting towards it.
+#[derive(Debug)]
+pub struct FaultyFile<T> {
+    inner: T,
+    state: Arc<Mutex<State>>,
+    current: Option<Current>,
+}
+
+impl<T> FaultyFile<T>
+where
+    T: AsyncFile,
+{
+    pub fn new(inner: T, plan: FaultPlan) -> Self {
+        let state = State {
+            rules: plan.rules,
+            rng: plan.seed,
+            calls: HashMap::new(),
+        };
+
+        FaultyFile {
+            inner,
+            state: Arc::new(Mutex::new(state)),
+            current: None,
+        }
+    }
+
+    /// How many calls of `op` have been made, through this file or its clones.
+    pub fn calls(&self, op: FileOp) -> u64 {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        state.calls.get(&op).cloned().unwrap_or(0)
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn poll_op<R, F>(&mut self, op: FileOp, f: F) -> Poll<R, Error>
+    where
+        F: FnOnce(&mut T, Option<Fault>) -> Poll<R, Error>,
+    {
+        let restart = match self.current {
+            Some(ref current) => current.op != op,
+            None => true,
+        };
+
+        if restart {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+            self.current = Some(Current {
+                op,
+                fault: state.start(op),
+                started: Instant::now(),
+                not_ready: false,
+                wakeup: None,
+            });
+        }
+
+        let fault = {
+            let current = self.current.as_mut().unwrap();
+
+            match current.fault {
+                Some(Fault::NotReady) if !current.not_ready => {
+                    current.not_ready = true;
+                    task::current().notify();
+                    return Ok(Async::NotReady);
+                }
+                Some(Fault::Delay(delay)) if current.started.elapsed() < delay => {
+                    // A delay too long to add to an `Instant` never ends, so needs no wake-up.
+                    if let Some(done_at) = current.started.checked_add(delay) {
+                        timer::wake_at(&mut current.wakeup, done_at);
+                    }
+
+                    return Ok(Async::NotReady);
+                }
+                _ => (),
+            }
+
+            current.fault
+        };
+
+        if let Some(e) = fault.as_ref().and_then(Fault::error) {
+            self.current = None;
+            return Err(e);
+        }
+
+        let res = f(&mut self.inner, fault);
+
+        if let Ok(Async::NotReady) = res {
+            return res;
+        }
+
+        self.current = None;
+        res
+    }
+}
+
+fn short(buf: &[u8], fault: Option<Fault>) -> &[u8] {
+    match fault {
+        Some(Fault::ShortWrite(n)) => &buf[..cmp::min(n, buf.len())],
+        _ => buf,
+    }
+}
+
+impl<T> AsyncFile for FaultyFile<T>
+where
+    T: AsyncFile,
+{
+    type Cloned = FaultyFile<T::Cloned>;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        self.poll_op(FileOp::Seek, |file, _| file.poll_seek(pos))
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::SyncAll, |file, _| file.poll_sync_all())
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::SyncData, |file, _| file.poll_sync_data())
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        self.poll_op(FileOp::SetLen, |file, _| file.poll_set_len(size))
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        self.poll_op(FileOp::Metadata, |file, _| file.poll_metadata())
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<Self::Cloned, Error> {
+        let cloned = try_ready!(self.poll_op(FileOp::TryClone, |file, _| file.poll_try_clone()));
+
+        Ok(Async::Ready(FaultyFile {
+            inner: cloned,
+            state: Arc::clone(&self.state),
+            current: None,
+        }))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        self.poll_op(FileOp::SetPermissions, |file, _| file.poll_set_permissions(perm))
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        self.poll_op(FileOp::Read, |file, _| file.poll_read(buf))
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        self.poll_op(FileOp::Write, |file, fault| file.poll_write(short(buf, fault)))
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        self.poll_op(FileOp::ReadAt, |file, _| file.poll_read_at(buf, offset))
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        self.poll_op(FileOp::WriteAt, |file, fault| {
+            file.poll_write_at(short(buf, fault), offset)
+        })
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_op(FileOp::Lock, |file, _| file.poll_lock(mode))
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_op(FileOp::TryLock, |file, _| file.poll_try_lock(mode))
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::Unlock, |file, _| file.poll_unlock())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.inner.path()
+    }
+
+    fn start_operation(&mut self) {
+        self.current = None;
+        self.inner.start_operation()
+    }
+}
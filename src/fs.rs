@@ -0,0 +1,215 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Futures that work on a path rather than an open file, mirroring `std::fs::read` and
+//! `std::fs::write`.
+
+use std::{io::Error, path::Path};
+
+use futures::{Async, Future, Poll};
+use tokio_fs::{file::OpenFuture, File, OpenOptions};
+
+use span::OpSpan;
+use {AsyncFile, ReadToEnd, SetLen, SyncPolicy, SyncWith, WriteAll};
+
+/// Read the entire contents of the file at `path`.
+pub fn read<P>(path: P) -> ReadFile<P>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let span = OpSpan::new("read_file", format_args!("")).with_path(path.as_ref());
+
+    ReadFile {
+        state: ReadState::Open(File::open(path)),
+        span,
+    }
+}
+
+/// Replace the contents of the file at `path` with `contents`, creating it if it doesn't exist.
+pub fn write<P, C>(path: P, contents: C) -> WriteFile<P, C>
+where
+    P: AsRef<Path> + Send + 'static,
+    C: AsRef<[u8]>,
+{
+    let span = OpSpan::new("write_file", format_args!("{} bytes", contents.as_ref().len()))
+        .with_path(path.as_ref());
+    let open = OpenOptions::new().write(true).create(true).open(path);
+
+    WriteFile::new(open, contents, false, span)
+}
+
+/// Add `contents` to the end of the file at `path`, creating it if it doesn't exist.
+///
+/// The file is opened in append mode, so each write lands at the end of the file even if another
+/// process appends to it at the same time.
+pub fn append<P, C>(path: P, contents: C) -> WriteFile<P, C>
+where
+    P: AsRef<Path> + Send + 'static,
+    C: AsRef<[u8]>,
+{
+    let span = OpSpan::new("append_file", format_args!("{} bytes", contents.as_ref().len()))
+        .with_path(path.as_ref());
+    let open = OpenOptions::new().append(true).create(true).open(path);
+
+    WriteFile::new(open, contents, true, span)
+}
+
+enum ReadState<P> {
+    Open(OpenFuture<P>),
+    Read(ReadToEnd<File>),
+}
+
+/// The future returned by `read`.
+pub struct ReadFile<P> {
+    state: ReadState<P>,
+    span: OpSpan,
+}
+
+impl<P> ReadFile<P>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    fn poll_steps(&mut self) -> Poll<Vec<u8>, Error> {
+        loop {
+            let next = match self.state {
+                ReadState::Open(ref mut open) => {
+                    let file = try_ready!(open.poll());
+
+                    ReadState::Read(file.read_to_end(u64::MAX))
+                }
+                ReadState::Read(ref mut read) => {
+                    let (_, buf) = try_ready!(read.poll());
+
+                    return Ok(Async::Ready(buf));
+                }
+            };
+
+            self.state = next;
+        }
+    }
+}
+
+impl<P> Future for ReadFile<P>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    type Item = Vec<u8>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let span = self.span.open(None);
+
+        span.poll(|| self.poll_steps())
+    }
+}
+
+enum WriteState<P, C> {
+    Open(OpenFuture<P>),
+    Truncate(SetLen<File>),
+    Write(WriteAll<File, C>),
+    Sync(SyncWith<File>),
+}
+
+/// The future returned by `write` and `append`.
+///
+/// Nothing is flushed to disk by default, as with `std::fs::write`; use `sync` to pick a
+/// `SyncPolicy` to apply once the contents are written.
+pub struct WriteFile<P, C> {
+    append: bool,
+    policy: SyncPolicy,
+    contents: Option<C>,
+    state: WriteState<P, C>,
+    span: OpSpan,
+}
+
+impl<P, C> WriteFile<P, C> {
+    fn new(open: OpenFuture<P>, contents: C, append: bool, span: OpSpan) -> Self {
+        WriteFile {
+            append,
+            policy: SyncPolicy::None,
+            contents: Some(contents),
+            state: WriteState::Open(open),
+            span,
+        }
+    }
+
+    /// Flush the file according to `policy` after writing to it.
+    pub fn sync(mut self, policy: SyncPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl<P, C> WriteFile<P, C>
+where
+    P: AsRef<Path> + Send + 'static,
+    C: AsRef<[u8]>,
+{
+    fn poll_steps(&mut self) -> Poll<(), Error> {
+        loop {
+            let next = match self.state {
+                WriteState::Open(ref mut open) => {
+                    let file = try_ready!(open.poll());
+
+                    if self.append {
+                        WriteState::Write(file.write_all(self.contents.take().unwrap()))
+                    } else {
+                        WriteState::Truncate(AsyncFile::set_len(file, 0))
+                    }
+                }
+                WriteState::Truncate(ref mut truncate) => {
+                    let file = try_ready!(truncate.poll());
+
+                    WriteState::Write(file.write_all(self.contents.take().unwrap()))
+                }
+                WriteState::Write(ref mut write) => {
+                    let (file, _, _) = try_ready!(write.poll());
+
+                    if self.policy == SyncPolicy::None {
+                        return Ok(Async::Ready(()));
+                    }
+
+                    WriteState::Sync(file.sync_with(self.policy.clone()))
+                }
+                WriteState::Sync(ref mut sync) => {
+                    try_ready!(sync.poll());
+
+                    return Ok(Async::Ready(()));
+                }
+            };
+
+            self.state = next;
+        }
+    }
+}
+
+impl<P, C> Future for WriteFile<P, C>
+where
+    P: AsRef<Path> + Send + 'static,
+    C: AsRef<[u8]>,
+{
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let span = self.span.open(None);
+
+        span.poll(|| self.poll_steps())
+    }
+}
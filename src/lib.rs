@@ -23,6 +23,69 @@
 //!
 //! There's really not much to it.
 //!
+//! Every future hands the file back when it finishes. That includes failures: the futures'
+//! error type is `FileError<T>`, which carries the file alongside the `io::Error`, so a failed
+//! operation doesn't mean reopening the file. `FileError<T>` converts into `io::Error` for when
+//! the file isn't needed anymore. To leave a file where it is, run the operation on `&mut file`
+//! instead, and the future hands back the borrow; see the `borrowed` module.
+//!
+//! `tokio_fs::File` only works inside a tokio runtime. Outside of one, wrap a `std::fs::File` in
+//! a `PooledFile`, which runs its operations on a small thread pool of its own. For tests,
+//! `MemFile` keeps its contents in memory and never touches the filesystem. Files report their
+//! metadata as a `FileMetadata`, which unlike `std::fs::Metadata` can be built by hand.
+//!
+//! With the `futures03` feature enabled, every future also implements `std::future::Future`.
+//! See the `std_future` module for the `AsyncFile` counterpart that polls with a `Context`.
+//! The `tokio1` feature adds `tokio1::File`, an `AsyncFile` for services running on tokio 1.
+//! The `tracing` feature runs each operation future in a `file_op` span, naming the operation,
+//! its arguments, the file's `path` if it's known, and how it turned out. The path-based futures,
+//! like `read` and `atomic_write`, get a span naming their path, around the spans of the
+//! operations they run.
+//!
+//! ### Cancellation
+//!
+//! Each future owns its file until it resolves, so dropping one drops the file too. To give up
+//! on an operation and keep the file, call the future's `into_inner` instead.
+//!
+//! Nothing an abandoned operation did is undone. Whatever steps finished before it reported
+//! `NotReady` stay finished: a `WriteAll` leaves the bytes it wrote so far in the file, with the
+//! cursor after them, and a `SyncWith` may have flushed the file but not its directory. The step
+//! in progress depends on the file. `tokio_fs::File` only reports `NotReady` before it starts a
+//! step, so nothing more happens. `PooledFile` finishes the step in the background, and the next
+//! operation on the file waits for it.
+//!
+//! ```rust
+//! # extern crate file_futures;
+//! # extern crate futures;
+//! use file_futures::{AsyncFile, Fault, FaultPlan, FaultyFile, FileOp, MemFile};
+//! use futures::{future, Future};
+//!
+//! # fn main() {
+//! // The first write only gets half way, and the second isn't ready yet.
+//! let plan = FaultPlan::new(0)
+//!     .on_call(FileOp::Write, 0, Fault::ShortWrite(3))
+//!     .on_call(FileOp::Write, 1, Fault::NotReady);
+//!
+//! let mut write = FaultyFile::new(MemFile::new(), plan).write_all(b"abcdef");
+//!
+//! let write = future::lazy(move || {
+//!     assert!(write.poll().unwrap().is_not_ready());
+//!     Ok::<_, ()>(write)
+//! })
+//! .wait()
+//! .unwrap();
+//!
+//! let (file, pos) = write
+//!     .into_inner()
+//!     .seek(std::io::SeekFrom::Current(0))
+//!     .wait()
+//!     .unwrap();
+//!
+//! assert_eq!(file.get_ref().contents(), b"abc");
+//! assert_eq!(pos, 3);
+//! # }
+//! ```
+//!
 //! ### Example
 //! ```rust
 //! # extern crate file_futures;
@@ -58,719 +121,745 @@
 //! }
 //! ```
 
+#[macro_use]
 extern crate futures;
+#[cfg(feature = "futures03")]
+extern crate futures03;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "tokio1")]
+extern crate tokio1 as tokio;
 extern crate tokio_fs;
-
-use std::{fs::{Metadata, Permissions}, io::{Error, SeekFrom}};
-use futures::{Async, Future, Poll};
+extern crate tokio_io;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+#[macro_use]
+mod macros;
+mod atomic;
+mod blocking;
+pub mod borrowed;
+mod driver;
+mod dynamic;
+mod error;
+mod fault;
+mod fs;
+mod lock;
+mod mem;
+mod metadata;
+mod metrics;
+mod named;
+mod offload;
+mod op;
+mod pool;
+mod read_write;
+mod record;
+mod retry;
+mod span;
+mod timeout;
+mod timer;
+#[cfg(feature = "futures03")]
+pub mod std_future;
+#[cfg(feature = "tokio1")]
+pub mod tokio1;
+
+use std::{fs::Permissions, io::{Error, SeekFrom}, path::{Path, PathBuf}, time::Duration};
+use futures::{future, Async, Poll};
+
+use driver::Driver;
+use span::OpSpan;
+
+pub use atomic::{atomic_write, AtomicFile, AtomicWrite, Commit, CreateAtomicFile};
+pub use dynamic::DynAsyncFile;
+pub use error::{FileError, LimitExceeded};
+pub use fault::{Fault, FaultPlan, FaultyFile};
+pub use fs::{append, read, write, ReadFile, WriteFile};
+pub use lock::{Lock, LockMode, LockedFile, Unlock};
+pub use mem::MemFile;
+pub use metadata::{FileKind, FileMetadata};
+pub use metrics::{FileMetrics, Histogram, InstrumentedFile, MemMetrics};
+pub use named::{NamedFile, PathError};
+pub use op::FileOp;
+pub use pool::{Pool, PooledFile};
+pub use read_write::{Read, ReadAt, ReadExact, ReadToEnd, ReadToString, Write, WriteAll, WriteAt};
+pub use record::{
+    RecordedCall, RecordedError, RecordedValue, RecordingFile, ReplayFile, TraceEvent,
+};
+pub use retry::{RetriesExhausted, RetryPolicy, RetryingFile};
+pub use timeout::TimedFile;
 
 /// The trait that provides the futures associated with `tokio_fs::File`'s poll methods.
 pub trait AsyncFile: Sized {
+    /// The new handle `try_clone` resolves to.
+    type Cloned: AsyncFile;
+
     fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error>;
     fn poll_sync_all(&mut self) -> Poll<(), Error>;
     fn poll_sync_data(&mut self) -> Poll<(), Error>;
     fn poll_set_len(&mut self, size: u64) -> Poll<(), Error>;
-    fn poll_metadata(&mut self) -> Poll<Metadata, Error>;
-    fn poll_try_clone(&mut self) -> Poll<tokio_fs::file::File, Error>;
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error>;
+    fn poll_try_clone(&mut self) -> Poll<Self::Cloned, Error>;
     fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error>;
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error>;
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error>;
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error>;
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error>;
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error>;
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error>;
+    fn poll_unlock(&mut self) -> Poll<(), Error>;
 
-    fn seek(self, pos: SeekFrom) -> Seek<Self> {
-        Seek {
-            pos,
-            inner: Some(self),
-        }
+    /// The path the file was opened at, if it's known, as it is for a `NamedFile`. Only used to
+    /// describe the file, e.g. in `tracing` spans.
+    fn path(&self) -> Option<&Path> {
+        None
     }
 
-    fn sync_all(self) -> SyncAll<Self> {
-        SyncAll { inner: Some(self) }
-    }
+    /// Called by the methods below as they create an operation future, before it's polled.
+    ///
+    /// Files that keep track of an operation across `NotReady`, like `TimedFile`, use this to
+    /// forget one left behind by a future that was abandoned with `into_inner`. Wrappers pass
+    /// it on to the file they wrap.
+    fn start_operation(&mut self) {}
 
-    fn sync_data(self) -> SyncData<Self> {
-        SyncData { inner: Some(self) }
+    fn seek(mut self, pos: SeekFrom) -> Seek<Self> {
+        self.start_operation();
+        Seek::new(self, pos)
     }
 
-    fn set_len(self, size: u64) -> SetLen<Self> {
-        SetLen {
-            size,
-            inner: Some(self),
-        }
+    fn sync_all(mut self) -> SyncAll<Self> {
+        self.start_operation();
+        SyncAll::new(self)
     }
 
-    fn metadata(self) -> GetMetadata<Self> {
-        GetMetadata { inner: Some(self) }
+    fn sync_data(mut self) -> SyncData<Self> {
+        self.start_operation();
+        SyncData::new(self)
     }
 
-    fn try_clone(self) -> TryClone<Self> {
-        TryClone { inner: Some(self) }
+    fn set_len(mut self, size: u64) -> SetLen<Self> {
+        self.start_operation();
+        SetLen::new(self, size)
     }
 
-    fn set_permissions(self, perm: Permissions) -> SetPermissions<Self> {
-        SetPermissions {
-            perm,
-            inner: Some(self),
-        }
+    fn metadata(mut self) -> GetMetadata<Self> {
+        self.start_operation();
+        GetMetadata::new(self)
     }
-}
 
-impl AsyncFile for tokio_fs::file::File {
-    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
-        tokio_fs::file::File::poll_seek(self, pos)
+    fn try_clone(mut self) -> TryClone<Self> {
+        self.start_operation();
+        TryClone::new(self)
     }
 
-    fn poll_sync_all(&mut self) -> Poll<(), Error> {
-        tokio_fs::file::File::poll_sync_all(self)
+    fn set_permissions(mut self, perm: Permissions) -> SetPermissions<Self> {
+        self.start_operation();
+        SetPermissions::new(self, perm)
     }
 
-    fn poll_sync_data(&mut self) -> Poll<(), Error> {
-        tokio_fs::file::File::poll_sync_data(self)
+    /// Read some bytes into `buf`, resolving to the file, the buffer and the number of bytes read.
+    fn read<B>(mut self, buf: B) -> Read<Self, B>
+    where
+        B: AsMut<[u8]>,
+    {
+        self.start_operation();
+        Read::new(self, buf)
     }
 
-    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
-        tokio_fs::file::File::poll_set_len(self, size)
+    /// Write some bytes from `buf`, resolving to the file, the buffer and the number of bytes
+    /// written.
+    fn write<B>(mut self, buf: B) -> Write<Self, B>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.start_operation();
+        Write::new(self, buf)
     }
 
-    fn poll_metadata(&mut self) -> Poll<Metadata, Error> {
-        tokio_fs::file::File::poll_metadata(self)
+    /// Fill all of `buf`, failing with `UnexpectedEof` if the file ends first.
+    fn read_exact<B>(mut self, buf: B) -> ReadExact<Self, B>
+    where
+        B: AsMut<[u8]>,
+    {
+        self.start_operation();
+        ReadExact::new(self, buf)
     }
 
-    fn poll_try_clone(&mut self) -> Poll<tokio_fs::file::File, Error> {
-        tokio_fs::file::File::poll_try_clone(self)
+    /// Write all of `buf`, failing with `WriteZero` if the file stops accepting bytes.
+    fn write_all<B>(mut self, buf: B) -> WriteAll<Self, B>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.start_operation();
+        WriteAll::new(self, buf)
     }
 
-    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
-        tokio_fs::file::File::poll_set_permissions(self, perm)
+    /// Read some bytes into `buf` from `offset`, without using or moving the file's cursor.
+    ///
+    /// Resolves to the file, the buffer and the number of bytes read.
+    fn read_at<B>(mut self, offset: u64, buf: B) -> ReadAt<Self, B>
+    where
+        B: AsMut<[u8]>,
+    {
+        self.start_operation();
+        ReadAt::new(self, offset, buf)
     }
-}
-
-pub struct Seek<T> {
-    pos: SeekFrom,
-    inner: Option<T>,
-}
-
-impl<T> Future for Seek<T>
-where
-    T: AsyncFile,
-{
-    type Item = (T, u64);
-    type Error = Error;
-
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let mut inner = self.inner.take().unwrap();
 
-        match inner.poll_seek(self.pos) {
-            Ok(Async::Ready(seek)) => Ok(Async::Ready((inner, seek))),
-            Ok(_) => Ok(Async::NotReady),
-            Err(e) => Err(e),
-        }
+    /// Write some bytes from `buf` at `offset`, without using or moving the file's cursor.
+    ///
+    /// Resolves to the file, the buffer and the number of bytes written.
+    fn write_at<B>(mut self, offset: u64, buf: B) -> WriteAt<Self, B>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.start_operation();
+        WriteAt::new(self, offset, buf)
     }
-}
-
-impl<T> AsyncFile for Seek<T>
-where
-    T: AsyncFile,
-{
-    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_seek(pos);
-        self.inner = Some(inner);
 
-        res
+    /// Read the rest of the file from the cursor on, failing with `LimitExceeded` if more than
+    /// `limit` bytes are left.
+    fn read_to_end(mut self, limit: u64) -> ReadToEnd<Self> {
+        self.start_operation();
+        ReadToEnd::new(self, limit)
     }
 
-    fn poll_sync_all(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_all();
-        self.inner = Some(inner);
-
-        res
+    /// Read the rest of the file from the cursor on as UTF-8, failing with `LimitExceeded` if
+    /// more than `limit` bytes are left.
+    fn read_to_string(mut self, limit: u64) -> ReadToString<Self> {
+        self.start_operation();
+        ReadToString::new(self, limit)
     }
 
-    fn poll_sync_data(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_data();
-        self.inner = Some(inner);
-
-        res
+    /// Wait for a shared advisory lock on the file.
+    fn lock_shared(mut self) -> Lock<Self> {
+        self.start_operation();
+        Lock::new(self, LockMode::Shared, true)
     }
 
-    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_len(size);
-        self.inner = Some(inner);
-
-        res
+    /// Wait for an exclusive advisory lock on the file.
+    fn lock_exclusive(mut self) -> Lock<Self> {
+        self.start_operation();
+        Lock::new(self, LockMode::Exclusive, true)
     }
 
-    fn poll_metadata(&mut self) -> Poll<Metadata, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_metadata();
-        self.inner = Some(inner);
-
-        res
+    /// Take a shared advisory lock on the file, failing with `WouldBlock` if that would mean
+    /// waiting.
+    fn try_lock_shared(mut self) -> Lock<Self> {
+        self.start_operation();
+        Lock::new(self, LockMode::Shared, false)
     }
 
-    fn poll_try_clone(&mut self) -> Poll<tokio_fs::file::File, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_try_clone();
-        self.inner = Some(inner);
-
-        res
+    /// Take an exclusive advisory lock on the file, failing with `WouldBlock` if that would mean
+    /// waiting.
+    fn try_lock_exclusive(mut self) -> Lock<Self> {
+        self.start_operation();
+        Lock::new(self, LockMode::Exclusive, false)
     }
 
-    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
+    /// Release any advisory lock held on the file.
+    fn unlock(mut self) -> Unlock<Self> {
+        self.start_operation();
+        Unlock::new(self)
+    }
 
-        let res = inner.poll_set_permissions(perm);
-        self.inner = Some(inner);
+    /// Flush the file to disk as thoroughly as `policy` asks for.
+    fn sync_with(mut self, policy: SyncPolicy) -> SyncWith<Self> {
+        self.start_operation();
+        SyncWith::new(self, policy)
+    }
 
-        res
+    /// Fail each operation on the file with `TimedOut` once it has taken longer than `timeout`.
+    fn with_timeout(self, timeout: Duration) -> TimedFile<Self> {
+        TimedFile::new(self, timeout)
     }
 }
 
-pub struct SyncAll<T> {
-    inner: Option<T>,
+/// How much durability to pay for when flushing a file.
+///
+/// This lets storage code pick a durability level from configuration rather than hard-coding
+/// which of `sync_all` or `sync_data` to call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Don't flush anything.
+    None,
+    /// Flush the file's contents, like `sync_data`.
+    Data,
+    /// Flush the file's contents and metadata, like `sync_all`.
+    All,
+    /// Flush the file's contents, then the given directory.
+    ///
+    /// Syncing the directory containing a file makes a newly created or renamed entry durable.
+    DataThenDirectory(PathBuf),
 }
 
-impl<T> Future for SyncAll<T>
-where
-    T: AsyncFile,
-{
-    type Item = T;
-    type Error = Error;
+/// Gives `delegate_async_file!` access to the file a future or adapter is holding on to.
+trait Inner {
+    type File: AsyncFile;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let mut inner = self.inner.take().unwrap();
+    fn inner_ref(&self) -> &Self::File;
 
-        match inner.poll_sync_all() {
-            Ok(Async::Ready(())) => Ok(Async::Ready(inner)),
-            Ok(_) => Ok(Async::NotReady),
-            Err(e) => Err(e),
-        }
-    }
+    fn inner_mut(&mut self) -> &mut Self::File;
 }
 
-impl<T> AsyncFile for SyncAll<T>
-where
-    T: AsyncFile,
-{
-    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_seek(pos);
-        self.inner = Some(inner);
+impl AsyncFile for tokio_fs::file::File {
+    type Cloned = tokio_fs::file::File;
 
-        res
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        tokio_fs::file::File::poll_seek(self, pos)
     }
 
     fn poll_sync_all(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_all();
-        self.inner = Some(inner);
-
-        res
+        tokio_fs::file::File::poll_sync_all(self)
     }
 
     fn poll_sync_data(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_data();
-        self.inner = Some(inner);
-
-        res
+        tokio_fs::file::File::poll_sync_data(self)
     }
 
     fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_len(size);
-        self.inner = Some(inner);
-
-        res
+        tokio_fs::file::File::poll_set_len(self, size)
     }
 
-    fn poll_metadata(&mut self) -> Poll<Metadata, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_metadata();
-        self.inner = Some(inner);
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        let metadata = try_ready!(tokio_fs::file::File::poll_metadata(self));
 
-        res
+        Ok(Async::Ready(metadata.into()))
     }
 
-    fn poll_try_clone(&mut self) -> Poll<tokio_fs::file::File, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_try_clone();
-        self.inner = Some(inner);
-
-        res
+    fn poll_try_clone(&mut self) -> Poll<Self::Cloned, Error> {
+        tokio_fs::file::File::poll_try_clone(self)
     }
 
     fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_permissions(perm);
-        self.inner = Some(inner);
-
-        res
-    }
-}
-
-pub struct SyncData<T> {
-    inner: Option<T>,
-}
-
-impl<T> Future for SyncData<T>
-where
-    T: AsyncFile,
-{
-    type Item = T;
-    type Error = Error;
-
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        match inner.poll_sync_all() {
-            Ok(Async::Ready(())) => Ok(Async::Ready(inner)),
-            Ok(_) => Ok(Async::NotReady),
-            Err(e) => Err(e),
-        }
+        tokio_fs::file::File::poll_set_permissions(self, perm)
     }
-}
-
-impl<T> AsyncFile for SyncData<T>
-where
-    T: AsyncFile,
-{
-    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_seek(pos);
-        self.inner = Some(inner);
 
-        res
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        tokio_io::AsyncRead::poll_read(self, buf)
     }
 
-    fn poll_sync_all(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_all();
-        self.inner = Some(inner);
-
-        res
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        tokio_io::AsyncWrite::poll_write(self, buf)
     }
 
-    fn poll_sync_data(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_data();
-        self.inner = Some(inner);
-
-        res
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        blocking::poll_std(self, |std| blocking::read_at(std, buf, offset))
     }
 
-    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_len(size);
-        self.inner = Some(inner);
-
-        res
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        blocking::poll_std(self, |std| blocking::write_at(std, buf, offset))
     }
 
-    fn poll_metadata(&mut self) -> Poll<Metadata, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_metadata();
-        self.inner = Some(inner);
-
-        res
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        blocking::poll_std(self, |std| lock::lock(std, mode))
     }
 
-    fn poll_try_clone(&mut self) -> Poll<tokio_fs::file::File, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_try_clone();
-        self.inner = Some(inner);
-
-        res
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        blocking::poll_std(self, |std| lock::try_lock(std, mode))
     }
 
-    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_permissions(perm);
-        self.inner = Some(inner);
-
-        res
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        blocking::poll_std(self, |std| std.unlock())
     }
 }
 
-pub struct SetLen<T> {
-    size: u64,
-    inner: Option<T>,
-}
-
-impl<T> Future for SetLen<T>
+/// Runs operations on a borrowed file, so a future resolves to the borrow rather than taking
+/// the file out of wherever it lives. `try_clone` still resolves to an owned clone.
+impl<T> AsyncFile for &mut T
 where
     T: AsyncFile,
 {
-    type Item = T;
-    type Error = Error;
-
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        match inner.poll_set_len(self.size) {
-            Ok(Async::Ready(())) => Ok(Async::Ready(inner)),
-            Ok(_) => Ok(Async::NotReady),
-            Err(e) => Err(e),
-        }
-    }
+    delegate_async_file!(@methods T);
 }
 
-impl<T> AsyncFile for SetLen<T>
+impl<T> Inner for &mut T
 where
     T: AsyncFile,
 {
-    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_seek(pos);
-        self.inner = Some(inner);
-
-        res
-    }
-
-    fn poll_sync_all(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_all();
-        self.inner = Some(inner);
-
-        res
-    }
-
-    fn poll_sync_data(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_data();
-        self.inner = Some(inner);
-
-        res
-    }
-
-    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_len(size);
-        self.inner = Some(inner);
-
-        res
-    }
-
-    fn poll_metadata(&mut self) -> Poll<Metadata, Error> {
-        let mut inner = self.inner.take().unwrap();
+    type File = T;
 
-        let res = inner.poll_metadata();
-        self.inner = Some(inner);
-
-        res
-    }
-
-    fn poll_try_clone(&mut self) -> Poll<tokio_fs::file::File, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_try_clone();
-        self.inner = Some(inner);
-
-        res
+    fn inner_ref(&self) -> &T {
+        self
     }
 
-    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_permissions(perm);
-        self.inner = Some(inner);
-
-        res
+    fn inner_mut(&mut self) -> &mut T {
+        self
     }
 }
 
-pub struct GetMetadata<T> {
-    inner: Option<T>,
-}
-
-impl<T> Future for GetMetadata<T>
+impl<T> AsyncFile for Box<T>
 where
     T: AsyncFile,
 {
-    type Item = (T, Metadata);
-    type Error = Error;
-
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        match inner.poll_metadata() {
-            Ok(Async::Ready(metadata)) => Ok(Async::Ready((inner, metadata))),
-            Ok(_) => Ok(Async::NotReady),
-            Err(e) => Err(e),
-        }
-    }
+    delegate_async_file!(@methods T);
 }
 
-impl<T> AsyncFile for GetMetadata<T>
+impl<T> Inner for Box<T>
 where
     T: AsyncFile,
 {
-    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_seek(pos);
-        self.inner = Some(inner);
+    type File = T;
 
-        res
+    fn inner_ref(&self) -> &T {
+        self
     }
 
-    fn poll_sync_all(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_all();
-        self.inner = Some(inner);
-
-        res
-    }
-
-    fn poll_sync_data(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_data();
-        self.inner = Some(inner);
-
-        res
-    }
-
-    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_len(size);
-        self.inner = Some(inner);
-
-        res
-    }
-
-    fn poll_metadata(&mut self) -> Poll<Metadata, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_metadata();
-        self.inner = Some(inner);
-
-        res
+    fn inner_mut(&mut self) -> &mut T {
+        self
     }
+}
 
-    fn poll_try_clone(&mut self) -> Poll<tokio_fs::file::File, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_try_clone();
-        self.inner = Some(inner);
+pub struct Seek<T> {
+    pos: SeekFrom,
+    inner: Option<T>,
+    span: OpSpan,
+}
 
-        res
+impl<T> Seek<T> {
+    pub(crate) fn new(inner: T, pos: SeekFrom) -> Self {
+        Seek {
+            pos,
+            inner: Some(inner),
+            span: OpSpan::new("seek", format_args!("{:?}", pos)),
+        }
     }
 
-    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_permissions(perm);
-        self.inner = Some(inner);
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, u64), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match self.inner {
+            Some(ref mut inner) => driver.poll_seek(inner, self.pos),
+            None => panic!("Polled future after completion"),
+        };
 
-        res
+        match res {
+            Ok(Async::Ready(seek)) => Ok(Async::Ready((self.inner.take().unwrap(), seek))),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => Err(FileError::new("seek", e, self.inner.take().unwrap())),
+        }
     }
 }
 
-pub struct TryClone<T> {
+pub struct SyncAll<T> {
     inner: Option<T>,
+    span: OpSpan,
 }
 
-impl<T> Future for TryClone<T>
-where
-    T: AsyncFile,
-{
-    type Item = (T, tokio_fs::file::File);
-    type Error = Error;
+impl<T> SyncAll<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        SyncAll {
+            inner: Some(inner),
+            span: OpSpan::new("sync_all", format_args!("")),
+        }
+    }
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let mut inner = self.inner.take().unwrap();
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<T, FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match self.inner {
+            Some(ref mut inner) => driver.poll_sync_all(inner),
+            None => panic!("Polled future after completion"),
+        };
 
-        match inner.poll_try_clone() {
-            Ok(Async::Ready(file)) => Ok(Async::Ready((inner, file))),
+        match res {
+            Ok(Async::Ready(())) => Ok(Async::Ready(self.inner.take().unwrap())),
             Ok(_) => Ok(Async::NotReady),
-            Err(e) => Err(e),
+            Err(e) => Err(FileError::new("sync_all", e, self.inner.take().unwrap())),
         }
     }
 }
 
-impl<T> AsyncFile for TryClone<T>
-where
-    T: AsyncFile,
-{
-    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_seek(pos);
-        self.inner = Some(inner);
-
-        res
-    }
-
-    fn poll_sync_all(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_all();
-        self.inner = Some(inner);
+pub struct SyncData<T> {
+    inner: Option<T>,
+    span: OpSpan,
+}
 
-        res
+impl<T> SyncData<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        SyncData {
+            inner: Some(inner),
+            span: OpSpan::new("sync_data", format_args!("")),
+        }
     }
 
-    fn poll_sync_data(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<T, FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match self.inner {
+            Some(ref mut inner) => driver.poll_sync_data(inner),
+            None => panic!("Polled future after completion"),
+        };
 
-        let res = inner.poll_sync_data();
-        self.inner = Some(inner);
-
-        res
+        match res {
+            Ok(Async::Ready(())) => Ok(Async::Ready(self.inner.take().unwrap())),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => Err(FileError::new("sync_data", e, self.inner.take().unwrap())),
+        }
     }
+}
 
-    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_len(size);
-        self.inner = Some(inner);
+pub struct SetLen<T> {
+    size: u64,
+    inner: Option<T>,
+    span: OpSpan,
+}
 
-        res
+impl<T> SetLen<T> {
+    pub(crate) fn new(inner: T, size: u64) -> Self {
+        SetLen {
+            size,
+            inner: Some(inner),
+            span: OpSpan::new("set_len", format_args!("{}", size)),
+        }
     }
 
-    fn poll_metadata(&mut self) -> Poll<Metadata, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_metadata();
-        self.inner = Some(inner);
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<T, FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match self.inner {
+            Some(ref mut inner) => driver.poll_set_len(inner, self.size),
+            None => panic!("Polled future after completion"),
+        };
 
-        res
+        match res {
+            Ok(Async::Ready(())) => Ok(Async::Ready(self.inner.take().unwrap())),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => Err(FileError::new("set_len", e, self.inner.take().unwrap())),
+        }
     }
+}
 
-    fn poll_try_clone(&mut self) -> Poll<tokio_fs::file::File, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_try_clone();
-        self.inner = Some(inner);
+pub struct GetMetadata<T> {
+    inner: Option<T>,
+    span: OpSpan,
+}
 
-        res
+impl<T> GetMetadata<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        GetMetadata {
+            inner: Some(inner),
+            span: OpSpan::new("metadata", format_args!("")),
+        }
     }
 
-    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_permissions(perm);
-        self.inner = Some(inner);
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, FileMetadata), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match self.inner {
+            Some(ref mut inner) => driver.poll_metadata(inner),
+            None => panic!("Polled future after completion"),
+        };
 
-        res
+        match res {
+            Ok(Async::Ready(metadata)) => Ok(Async::Ready((self.inner.take().unwrap(), metadata))),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => Err(FileError::new("metadata", e, self.inner.take().unwrap())),
+        }
     }
 }
 
-pub struct SetPermissions<T> {
-    perm: Permissions,
+pub struct TryClone<T> {
     inner: Option<T>,
+    span: OpSpan,
 }
 
-impl<T> Future for SetPermissions<T>
-where
-    T: AsyncFile,
-{
-    type Item = T;
-    type Error = Error;
+impl<T> TryClone<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        TryClone {
+            inner: Some(inner),
+            span: OpSpan::new("try_clone", format_args!("")),
+        }
+    }
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let mut inner = self.inner.take().unwrap();
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, D::Cloned), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match self.inner {
+            Some(ref mut inner) => driver.poll_try_clone(inner),
+            None => panic!("Polled future after completion"),
+        };
 
-        match inner.poll_set_permissions(self.perm.clone()) {
-            Ok(Async::Ready(())) => Ok(Async::Ready(inner)),
+        match res {
+            Ok(Async::Ready(file)) => Ok(Async::Ready((self.inner.take().unwrap(), file))),
             Ok(_) => Ok(Async::NotReady),
-            Err(e) => Err(e),
+            Err(e) => Err(FileError::new("try_clone", e, self.inner.take().unwrap())),
         }
     }
 }
 
-impl<T> AsyncFile for SetPermissions<T>
+// Not in `operation_future!`, since what it resolves to depends on which `AsyncFile` polls it.
+impl<T> futures::Future for TryClone<T>
 where
     T: AsyncFile,
 {
-    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
-        let mut inner = self.inner.take().unwrap();
+    type Item = (T, T::Cloned);
+    type Error = FileError<T>;
 
-        let res = inner.poll_seek(pos);
-        self.inner = Some(inner);
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let span = self.span.open(self.file().and_then(AsyncFile::path));
 
-        res
+        span.poll(|| self.poll_with(&mut driver::Direct))
     }
+}
 
-    fn poll_sync_all(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
+pub struct SetPermissions<T> {
+    perm: Permissions,
+    inner: Option<T>,
+    span: OpSpan,
+}
 
-        let res = inner.poll_sync_all();
-        self.inner = Some(inner);
+impl<T> SetPermissions<T> {
+    pub(crate) fn new(inner: T, perm: Permissions) -> Self {
+        let span = OpSpan::new("set_permissions", format_args!("{:?}", perm));
 
-        res
+        SetPermissions {
+            perm,
+            inner: Some(inner),
+            span,
+        }
     }
 
-    fn poll_sync_data(&mut self) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_sync_data();
-        self.inner = Some(inner);
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<T, FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match self.inner {
+            Some(ref mut inner) => driver.poll_set_permissions(inner, self.perm.clone()),
+            None => panic!("Polled future after completion"),
+        };
 
-        res
+        match res {
+            Ok(Async::Ready(())) => Ok(Async::Ready(self.inner.take().unwrap())),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => Err(FileError::new("set_permissions", e, self.inner.take().unwrap())),
+        }
     }
+}
 
-    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_len(size);
-        self.inner = Some(inner);
-
-        res
-    }
+enum SyncState {
+    File,
+    OpenDirectory(tokio_fs::file::OpenFuture<PathBuf>),
+    SyncDirectory(tokio_fs::file::File),
+}
 
-    fn poll_metadata(&mut self) -> Poll<Metadata, Error> {
-        let mut inner = self.inner.take().unwrap();
+pub struct SyncWith<T> {
+    policy: SyncPolicy,
+    state: SyncState,
+    inner: Option<T>,
+    span: OpSpan,
+}
 
-        let res = inner.poll_metadata();
-        self.inner = Some(inner);
+impl<T> SyncWith<T> {
+    pub(crate) fn new(inner: T, policy: SyncPolicy) -> Self {
+        let span = OpSpan::new("sync_with", format_args!("{:?}", policy));
 
-        res
+        SyncWith {
+            policy,
+            state: SyncState::File,
+            inner: Some(inner),
+            span,
+        }
     }
 
-    fn poll_try_clone(&mut self) -> Poll<tokio_fs::file::File, Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_try_clone();
-        self.inner = Some(inner);
-
-        res
+    /// Drive the current step, returning the step to move on to, or `None` once finished.
+    fn poll_step<D>(&mut self, driver: &mut D) -> (&'static str, Poll<Option<SyncState>, Error>)
+    where
+        D: Driver<T>,
+    {
+        match self.state {
+            SyncState::File => {
+                let inner = match self.inner {
+                    Some(ref mut inner) => inner,
+                    None => panic!("Polled future after completion"),
+                };
+
+                let (operation, res) = match self.policy {
+                    SyncPolicy::None => ("sync_with", Ok(Async::Ready(()))),
+                    SyncPolicy::All => ("sync_all", driver.poll_sync_all(inner)),
+                    SyncPolicy::Data | SyncPolicy::DataThenDirectory(_) => {
+                        ("sync_data", driver.poll_sync_data(inner))
+                    }
+                };
+
+                let policy = &self.policy;
+                let res = res.map(|ready| {
+                    ready.map(|()| match *policy {
+                        SyncPolicy::DataThenDirectory(ref dir) => {
+                            Some(SyncState::OpenDirectory(tokio_fs::File::open(dir.clone())))
+                        }
+                        _ => None,
+                    })
+                });
+
+                (operation, res)
+            }
+            SyncState::OpenDirectory(ref mut open) => {
+                let res = driver
+                    .poll_future(open)
+                    .map(|ready| ready.map(|dir| Some(SyncState::SyncDirectory(dir))));
+
+                ("open_directory", res)
+            }
+            SyncState::SyncDirectory(ref mut dir) => {
+                let res = driver
+                    .poll_future(&mut future::poll_fn(|| dir.poll_sync_all()))
+                    .map(|ready| ready.map(|()| None));
+
+                ("sync_directory", res)
+            }
+        }
     }
 
-    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
-        let mut inner = self.inner.take().unwrap();
-
-        let res = inner.poll_set_permissions(perm);
-        self.inner = Some(inner);
-
-        res
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<T, FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        loop {
+            match self.poll_step(driver) {
+                (_, Ok(Async::Ready(Some(state)))) => self.state = state,
+                (_, Ok(Async::Ready(None))) => return Ok(Async::Ready(self.inner.take().unwrap())),
+                (_, Ok(_)) => return Ok(Async::NotReady),
+                (operation, Err(e)) => {
+                    return Err(FileError::new(operation, e, self.inner.take().unwrap()))
+                }
+            }
+        }
     }
 }
+
+delegate_async_file!(
+    Seek<T>,
+    SyncAll<T>,
+    SyncData<T>,
+    SetLen<T>,
+    GetMetadata<T>,
+    TryClone<T>,
+    SetPermissions<T>,
+    SyncWith<T>,
+);
+
+file_accessors!(
+    Seek<T>,
+    SyncAll<T>,
+    SyncData<T>,
+    SetLen<T>,
+    GetMetadata<T>,
+    TryClone<T>,
+    SetPermissions<T>,
+    SyncWith<T>,
+);
+
+operation_future!(
+    Seek<T> => (T, u64),
+    SyncAll<T> => T,
+    SyncData<T> => T,
+    SetLen<T> => T,
+    GetMetadata<T> => (T, FileMetadata),
+    SetPermissions<T> => T,
+    SyncWith<T> => T,
+);
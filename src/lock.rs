@@ -0,0 +1,187 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{fs::File as StdFile, io::{Error, Result}};
+
+use futures::{Async, Poll};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use driver::Driver;
+use span::OpSpan;
+use {AsyncFile, FileError, Inner};
+
+/// Which kind of advisory lock to take on a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LockMode {
+    /// Any number of handles may hold a shared lock at once.
+    Shared,
+    /// Only one handle may hold an exclusive lock, and no shared locks may be held alongside it.
+    Exclusive,
+}
+
+pub(crate) fn lock(file: &StdFile, mode: LockMode) -> Result<()> {
+    match mode {
+        LockMode::Shared => file.lock_shared(),
+        LockMode::Exclusive => file.lock(),
+    }
+}
+
+pub(crate) fn try_lock(file: &StdFile, mode: LockMode) -> Result<()> {
+    let res = match mode {
+        LockMode::Shared => file.try_lock_shared(),
+        LockMode::Exclusive => file.try_lock(),
+    };
+
+    res.map_err(Error::from)
+}
+
+/// Takes an advisory lock on a file, resolving to a `LockedFile` that holds it.
+///
+/// Waiting for the lock happens on the blocking pool. When created by one of the `try_lock_*`
+/// methods, it fails with `WouldBlock` instead of waiting.
+pub struct Lock<T> {
+    mode: LockMode,
+    wait: bool,
+    inner: Option<T>,
+    span: OpSpan,
+}
+
+impl<T> Lock<T> {
+    pub(crate) fn new(inner: T, mode: LockMode, wait: bool) -> Self {
+        let op = if wait { "lock" } else { "try_lock" };
+
+        Lock {
+            mode,
+            wait,
+            inner: Some(inner),
+            span: OpSpan::new(op, format_args!("{:?}", mode)),
+        }
+    }
+
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<LockedFile<T>, FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match self.inner {
+            Some(ref mut inner) if self.wait => driver.poll_lock(inner, self.mode),
+            Some(ref mut inner) => driver.poll_try_lock(inner, self.mode),
+            None => panic!("Polled future after completion"),
+        };
+
+        match res {
+            Ok(Async::Ready(())) => Ok(Async::Ready(LockedFile {
+                mode: self.mode,
+                release: driver.release(),
+                inner: self.inner.take(),
+            })),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => {
+                let operation = if self.wait { "lock" } else { "try_lock" };
+
+                Err(FileError::new(operation, e, self.inner.take().unwrap()))
+            }
+        }
+    }
+}
+
+/// Releases any advisory lock held on a file.
+pub struct Unlock<T> {
+    inner: Option<T>,
+    span: OpSpan,
+}
+
+impl<T> Unlock<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Unlock {
+            inner: Some(inner),
+            span: OpSpan::new("unlock", format_args!("")),
+        }
+    }
+
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<T, FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match self.inner {
+            Some(ref mut inner) => driver.poll_unlock(inner),
+            None => panic!("Polled future after completion"),
+        };
+
+        match res {
+            Ok(Async::Ready(())) => Ok(Async::Ready(self.inner.take().unwrap())),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => Err(FileError::new("unlock", e, self.inner.take().unwrap())),
+        }
+    }
+}
+
+/// A file holding an advisory lock.
+///
+/// Use `unlock` to release the lock and get the file back. Dropping a `LockedFile` also
+/// releases the lock, but only on a best-effort basis: drop can't wait, so if the file's
+/// `poll_unlock` isn't ready straight away the lock is left for the OS to release when the last
+/// handle to the file is closed.
+pub struct LockedFile<T> {
+    mode: LockMode,
+    release: fn(&mut T),
+    inner: Option<T>,
+}
+
+impl<T> LockedFile<T> {
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+
+    /// Release the lock, resolving to the file.
+    pub fn unlock(mut self) -> Unlock<T> {
+        Unlock::new(self.inner.take().unwrap())
+    }
+}
+
+impl<T> Inner for LockedFile<T>
+where
+    T: AsyncFile,
+{
+    type File = T;
+
+    fn inner_ref(&self) -> &T {
+        self.inner.as_ref().unwrap()
+    }
+
+    fn inner_mut(&mut self) -> &mut T {
+        self.inner.as_mut().unwrap()
+    }
+}
+
+delegate_async_file!(impl LockedFile<T>);
+
+impl<T> Drop for LockedFile<T> {
+    fn drop(&mut self) {
+        if let Some(ref mut inner) = self.inner {
+            (self.release)(inner);
+        }
+    }
+}
+
+delegate_async_file!(Lock<T>, Unlock<T>);
+file_accessors!(Lock<T>, Unlock<T>);
+
+operation_future!(Lock<T> => LockedFile<T>, Unlock<T> => T);
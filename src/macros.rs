@@ -0,0 +1,266 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/// Implement `AsyncFile` for a wrapper type by forwarding every `poll_*` method to the file
+/// it holds.
+///
+/// Passing the types, e.g. `Seek<T>` or `Read<T, B>`, covers the operation futures, which keep
+/// their file in an `inner: Option<T>` field. `impl Name<T>` or `impl Name => File` only emits
+/// the `AsyncFile` impl, for wrappers that provide their own `Inner` impl. Adding `, path: field`
+/// to the latter reports the `PathBuf` in `field` as the file's path, rather than asking the
+/// file it wraps.
+///
+/// The calls go through `AsyncFile` explicitly, since `tokio_fs::File` has inherent methods of
+/// the same names that return different types.
+///
+/// New `AsyncFile` methods only need adding here to reach every wrapper.
+macro_rules! delegate_async_file {
+    (@methods $file:ty) => {
+        delegate_async_file!(@poll_methods $file);
+
+        fn path(&self) -> Option<&::std::path::Path> {
+            ::AsyncFile::path(::Inner::inner_ref(self))
+        }
+    };
+    (@poll_methods $file:ty) => {
+        type Cloned = <$file as ::AsyncFile>::Cloned;
+
+        fn poll_seek(
+            &mut self,
+            pos: ::std::io::SeekFrom,
+        ) -> ::futures::Poll<u64, ::std::io::Error> {
+            ::AsyncFile::poll_seek(::Inner::inner_mut(self), pos)
+        }
+
+        fn poll_sync_all(&mut self) -> ::futures::Poll<(), ::std::io::Error> {
+            ::AsyncFile::poll_sync_all(::Inner::inner_mut(self))
+        }
+
+        fn poll_sync_data(&mut self) -> ::futures::Poll<(), ::std::io::Error> {
+            ::AsyncFile::poll_sync_data(::Inner::inner_mut(self))
+        }
+
+        fn poll_set_len(&mut self, size: u64) -> ::futures::Poll<(), ::std::io::Error> {
+            ::AsyncFile::poll_set_len(::Inner::inner_mut(self), size)
+        }
+
+        fn poll_metadata(
+            &mut self,
+        ) -> ::futures::Poll<::FileMetadata, ::std::io::Error> {
+            ::AsyncFile::poll_metadata(::Inner::inner_mut(self))
+        }
+
+        fn poll_try_clone(&mut self) -> ::futures::Poll<Self::Cloned, ::std::io::Error> {
+            ::AsyncFile::poll_try_clone(::Inner::inner_mut(self))
+        }
+
+        fn poll_set_permissions(
+            &mut self,
+            perm: ::std::fs::Permissions,
+        ) -> ::futures::Poll<(), ::std::io::Error> {
+            ::AsyncFile::poll_set_permissions(::Inner::inner_mut(self), perm)
+        }
+
+        fn poll_read(&mut self, buf: &mut [u8]) -> ::futures::Poll<usize, ::std::io::Error> {
+            ::AsyncFile::poll_read(::Inner::inner_mut(self), buf)
+        }
+
+        fn poll_write(&mut self, buf: &[u8]) -> ::futures::Poll<usize, ::std::io::Error> {
+            ::AsyncFile::poll_write(::Inner::inner_mut(self), buf)
+        }
+
+        fn poll_read_at(
+            &mut self,
+            buf: &mut [u8],
+            offset: u64,
+        ) -> ::futures::Poll<usize, ::std::io::Error> {
+            ::AsyncFile::poll_read_at(::Inner::inner_mut(self), buf, offset)
+        }
+
+        fn poll_write_at(
+            &mut self,
+            buf: &[u8],
+            offset: u64,
+        ) -> ::futures::Poll<usize, ::std::io::Error> {
+            ::AsyncFile::poll_write_at(::Inner::inner_mut(self), buf, offset)
+        }
+
+        fn poll_lock(&mut self, mode: ::LockMode) -> ::futures::Poll<(), ::std::io::Error> {
+            ::AsyncFile::poll_lock(::Inner::inner_mut(self), mode)
+        }
+
+        fn poll_try_lock(&mut self, mode: ::LockMode) -> ::futures::Poll<(), ::std::io::Error> {
+            ::AsyncFile::poll_try_lock(::Inner::inner_mut(self), mode)
+        }
+
+        fn poll_unlock(&mut self) -> ::futures::Poll<(), ::std::io::Error> {
+            ::AsyncFile::poll_unlock(::Inner::inner_mut(self))
+        }
+
+        fn start_operation(&mut self) {
+            ::AsyncFile::start_operation(::Inner::inner_mut(self))
+        }
+    };
+    (impl $name:ident<T $(, $param:ident)*>) => {
+        impl<T $(, $param)*> ::AsyncFile for $name<T $(, $param)*>
+        where
+            T: ::AsyncFile,
+        {
+            delegate_async_file!(@methods T);
+        }
+    };
+    (impl $name:ident => $file:ty) => {
+        impl ::AsyncFile for $name {
+            delegate_async_file!(@methods $file);
+        }
+    };
+    (impl $name:ident => $file:ty, path: $path:ident) => {
+        impl ::AsyncFile for $name {
+            delegate_async_file!(@poll_methods $file);
+
+            fn path(&self) -> Option<&::std::path::Path> {
+                Some(&self.$path)
+            }
+        }
+    };
+    ($($name:ident<T $(, $param:ident)*>),* $(,)*) => {
+        $(
+            impl<T $(, $param)*> ::Inner for $name<T $(, $param)*>
+            where
+                T: ::AsyncFile,
+            {
+                type File = T;
+
+                fn inner_ref(&self) -> &T {
+                    match self.inner {
+                        Some(ref inner) => inner,
+                        None => panic!("Used future after completion"),
+                    }
+                }
+
+                fn inner_mut(&mut self) -> &mut T {
+                    match self.inner {
+                        Some(ref mut inner) => inner,
+                        None => panic!("Used future after completion"),
+                    }
+                }
+            }
+
+            delegate_async_file!(impl $name<T $(, $param)*>);
+        )*
+    };
+}
+
+/// Add `get_ref`, `get_mut` and `into_inner` to operation futures that keep their file in an
+/// `inner: Option<T>` field, given their types, e.g. `Seek<T>` or `Read<T, B>`.
+macro_rules! file_accessors {
+    ($($name:ident<T $(, $param:ident)*>),* $(,)*) => {
+        $(
+            impl<T $(, $param)*> $name<T $(, $param)*> {
+                /// The file the operation runs on.
+                ///
+                /// # Panics
+                ///
+                /// If the future has already resolved.
+                pub fn get_ref(&self) -> &T {
+                    match self.inner {
+                        Some(ref inner) => inner,
+                        None => panic!("Used future after completion"),
+                    }
+                }
+
+                /// The file the operation runs on.
+                ///
+                /// # Panics
+                ///
+                /// If the future has already resolved.
+                pub fn get_mut(&mut self) -> &mut T {
+                    match self.inner {
+                        Some(ref mut inner) => inner,
+                        None => panic!("Used future after completion"),
+                    }
+                }
+
+                /// Abandon the operation, handing back the file. See the crate docs on
+                /// cancellation for the state the file is left in.
+                ///
+                /// # Panics
+                ///
+                /// If the future has already resolved.
+                pub fn into_inner(self) -> T {
+                    match self.inner {
+                        Some(inner) => inner,
+                        None => panic!("Used future after completion"),
+                    }
+                }
+
+                pub(crate) fn file(&self) -> Option<&T> {
+                    self.inner.as_ref()
+                }
+            }
+        )*
+    };
+}
+
+/// Implement `Future` for operation futures whose state machine lives in a `poll_with` method,
+/// given each type and what it resolves to, e.g. `Read<T, B: AsMut<[u8]>> => (T, B, usize)`.
+///
+/// With the `futures03` feature this also implements `std::future::Future`, polling the file
+/// through `std_future::AsyncFile`.
+macro_rules! operation_future {
+    ($($name:ident<T $(, $param:ident: $bound:path)*> => $item:ty),* $(,)*) => {
+        $(
+            impl<T $(, $param)*> ::futures::Future for $name<T $(, $param)*>
+            where
+                T: ::AsyncFile,
+                $($param: $bound,)*
+            {
+                type Item = $item;
+                type Error = ::FileError<T>;
+
+                fn poll(&mut self) -> ::futures::Poll<Self::Item, Self::Error> {
+                    let span = self.span.open(self.file().and_then(::AsyncFile::path));
+
+                    span.poll(|| self.poll_with(&mut ::driver::Direct))
+                }
+            }
+
+            #[cfg(feature = "futures03")]
+            impl<T $(, $param)*> ::std::future::Future for $name<T $(, $param)*>
+            where
+                T: ::std_future::AsyncFile + Unpin,
+                $($param: $bound + Unpin,)*
+            {
+                type Output = ::std::result::Result<$item, ::FileError<T>>;
+
+                fn poll(
+                    mut self: ::std::pin::Pin<&mut Self>,
+                    cx: &mut ::std::task::Context,
+                ) -> ::std::task::Poll<Self::Output> {
+                    let this = &mut *self;
+                    let span = this.span.open(this.file().and_then(::std_future::AsyncFile::path));
+
+                    ::std_future::poll_with_context(cx, |driver| {
+                        span.poll(|| this.poll_with(driver))
+                    })
+                }
+            }
+        )*
+    };
+}
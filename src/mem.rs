@@ -0,0 +1,357 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    cmp,
+    fs::Permissions,
+    io::{Error, ErrorKind, SeekFrom},
+    sync::{Arc, Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+use futures::{
+    task::{self, Task},
+    Async, Poll,
+};
+
+use {AsyncFile, FileKind, FileMetadata, LockMode};
+
+/// A file that lives in memory, for testing code that's generic over `AsyncFile` without
+/// touching the filesystem.
+///
+/// It behaves like a regular file opened for reading and writing: reads and writes move a
+/// cursor, writing past the end fills the gap with zeros, and `set_len` leaves the cursor
+/// where it is. Every operation is ready as soon as it's polled, apart from `lock_*` waiting
+/// on a lock held through another handle.
+///
+/// `try_clone` resolves to a handle sharing both the contents and the cursor, as duplicating a
+/// real file descriptor does. `reopen` gives a handle sharing only the contents, like opening
+/// the same path again, which is what advisory locks contend between.
+#[derive(Debug)]
+pub struct MemFile {
+    handle: Arc<Mutex<Handle>>,
+}
+
+/// The contents, shared by every handle.
+#[derive(Debug)]
+struct Node {
+    data: Vec<u8>,
+    permissions: Option<Permissions>,
+    created: SystemTime,
+    modified: SystemTime,
+    changed: SystemTime,
+    lock: Option<(LockMode, usize)>,
+    waiting: Vec<Task>,
+}
+
+/// What `reopen` makes a new one of, and `try_clone` shares.
+#[derive(Debug)]
+struct Handle {
+    node: Arc<Mutex<Node>>,
+    pos: u64,
+    held: Option<LockMode>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // Nothing panics while holding these, short of running out of memory.
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn invalid_seek() -> Error {
+    Error::new(ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+}
+
+fn offset_to_index(offset: u64) -> Result<usize, Error> {
+    if offset > usize::MAX as u64 {
+        return Err(Error::new(ErrorKind::InvalidInput, "offset doesn't fit in memory"));
+    }
+
+    Ok(offset as usize)
+}
+
+impl MemFile {
+    /// Create an empty file.
+    pub fn new() -> Self {
+        MemFile::with_contents(Vec::new())
+    }
+
+    /// Create a file holding `data`, with its cursor at the start.
+    pub fn with_contents<B>(data: B) -> Self
+    where
+        B: Into<Vec<u8>>,
+    {
+        let now = SystemTime::now();
+        let node = Node {
+            data: data.into(),
+            permissions: None,
+            created: now,
+            modified: now,
+            changed: now,
+            lock: None,
+            waiting: Vec::new(),
+        };
+
+        MemFile::open(Arc::new(Mutex::new(node)))
+    }
+
+    fn open(node: Arc<Mutex<Node>>) -> Self {
+        MemFile {
+            handle: Arc::new(Mutex::new(Handle {
+                node,
+                pos: 0,
+                held: None,
+            })),
+        }
+    }
+
+    /// Open the same contents again, with a cursor of its own at the start.
+    ///
+    /// Advisory locks taken through the new handle contend with those taken through this one.
+    pub fn reopen(&self) -> Self {
+        MemFile::open(Arc::clone(&lock(&self.handle).node))
+    }
+
+    /// A copy of the file's contents.
+    pub fn contents(&self) -> Vec<u8> {
+        let handle = lock(&self.handle);
+        let contents = lock(&handle.node).data.clone();
+
+        contents
+    }
+
+    fn poll_lock_with(&mut self, mode: LockMode, wait: bool) -> Poll<(), Error> {
+        let mut handle = lock(&self.handle);
+        let held = handle.held.take();
+        let mut node = lock(&handle.node);
+
+        // Like `flock`, taking a lock through a handle that already holds one replaces it.
+        if let Some(held) = held {
+            node.release(held);
+        }
+
+        let available = match node.lock {
+            None => true,
+            Some((held, _)) => held == LockMode::Shared && mode == LockMode::Shared,
+        };
+
+        if !available {
+            if !wait {
+                return Err(Error::new(ErrorKind::WouldBlock, "file is locked"));
+            }
+
+            node.waiting.push(task::current());
+            return Ok(Async::NotReady);
+        }
+
+        node.lock = match node.lock {
+            Some((held, holders)) => Some((held, holders + 1)),
+            None => Some((mode, 1)),
+        };
+        drop(node);
+
+        handle.held = Some(mode);
+        Ok(Async::Ready(()))
+    }
+}
+
+impl Default for MemFile {
+    fn default() -> Self {
+        MemFile::new()
+    }
+}
+
+impl Node {
+    /// Record a change to the metadata, and to the contents if `contents` is set.
+    fn touch(&mut self, contents: bool) {
+        self.changed = SystemTime::now();
+
+        if contents {
+            self.modified = self.changed;
+        }
+    }
+
+    fn release(&mut self, mode: LockMode) {
+        self.lock = match self.lock {
+            Some((held, holders)) if held == mode && holders > 1 => Some((held, holders - 1)),
+            _ => None,
+        };
+
+        for task in self.waiting.drain(..) {
+            task.notify();
+        }
+    }
+
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+        if offset >= self.data.len() as u64 {
+            return Ok(0);
+        }
+
+        let start = offset as usize;
+        let n = cmp::min(buf.len(), self.data.len() - start);
+
+        buf[..n].copy_from_slice(&self.data[start..start + n]);
+        Ok(n)
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize, Error> {
+        let start = offset_to_index(offset)?;
+        let end = start.checked_add(buf.len()).ok_or_else(invalid_seek)?;
+
+        if end > self.data.len() {
+            self.data.resize(end, 0);
+        }
+
+        self.data[start..end].copy_from_slice(buf);
+        self.touch(true);
+        Ok(buf.len())
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        if let Some(held) = self.held.take() {
+            lock(&self.node).release(held);
+        }
+    }
+}
+
+impl AsyncFile for MemFile {
+    type Cloned = MemFile;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        let mut handle = lock(&self.handle);
+        let len = lock(&handle.node).data.len() as u64;
+
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                handle.pos = offset;
+                return Ok(Async::Ready(offset));
+            }
+            SeekFrom::End(offset) => (len, offset),
+            SeekFrom::Current(offset) => (handle.pos, offset),
+        };
+
+        let pos = if offset < 0 {
+            base.checked_sub(offset.unsigned_abs())
+        } else {
+            base.checked_add(offset as u64)
+        };
+
+        handle.pos = pos.ok_or_else(invalid_seek)?;
+        Ok(Async::Ready(handle.pos))
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        let size = offset_to_index(size)?;
+        let handle = lock(&self.handle);
+        let mut node = lock(&handle.node);
+
+        node.data.resize(size, 0);
+        node.touch(true);
+        Ok(Async::Ready(()))
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        let handle = lock(&self.handle);
+        let node = lock(&handle.node);
+
+        let mut metadata = FileMetadata::new(FileKind::File, node.data.len() as u64)
+            .with_nlink(1)
+            .with_created(node.created)
+            .with_modified(node.modified)
+            .with_changed(node.changed);
+
+        if let Some(ref permissions) = node.permissions {
+            metadata = metadata.with_permissions(permissions.clone());
+        }
+
+        Ok(Async::Ready(metadata))
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<MemFile, Error> {
+        Ok(Async::Ready(MemFile {
+            handle: Arc::clone(&self.handle),
+        }))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        let handle = lock(&self.handle);
+        let mut node = lock(&handle.node);
+
+        node.permissions = Some(perm);
+        node.touch(false);
+        Ok(Async::Ready(()))
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        let mut handle = lock(&self.handle);
+        let n = lock(&handle.node).read_at(buf, handle.pos)?;
+
+        handle.pos += n as u64;
+        Ok(Async::Ready(n))
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        let mut handle = lock(&self.handle);
+        let n = lock(&handle.node).write_at(buf, handle.pos)?;
+
+        handle.pos += n as u64;
+        Ok(Async::Ready(n))
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        let handle = lock(&self.handle);
+        let n = lock(&handle.node).read_at(buf, offset)?;
+
+        Ok(Async::Ready(n))
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        let handle = lock(&self.handle);
+        let n = lock(&handle.node).write_at(buf, offset)?;
+
+        Ok(Async::Ready(n))
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_lock_with(mode, true)
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_lock_with(mode, false)
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        let mut handle = lock(&self.handle);
+
+        if let Some(held) = handle.held.take() {
+            lock(&handle.node).release(held);
+        }
+
+        Ok(Async::Ready(()))
+    }
+}
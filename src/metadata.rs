@@ -0,0 +1,336 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs::{Metadata, Permissions},
+    time::SystemTime,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// What kind of thing a file is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    /// Anything else, like a socket or a device.
+    Other,
+}
+
+/// What `poll_metadata` reports about a file.
+///
+/// Unlike `std::fs::Metadata` this can be built by hand, so files that aren't backed by the
+/// filesystem can report it too. Start from `FileMetadata::new` and fill in whatever else is
+/// known with the `with_*` methods; anything left out reads as `None`.
+///
+/// With the `serde` feature enabled it can be serialized. `Permissions` can't be, so it's
+/// carried as the read-only flag and the mode, and rebuilt from the mode on unix.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FileMetadata {
+    len: u64,
+    kind: FileKind,
+    readonly: bool,
+    mode: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    permissions: Option<Permissions>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    ino: Option<u64>,
+    nlink: Option<u64>,
+    accessed: Option<SystemTime>,
+    modified: Option<SystemTime>,
+    changed: Option<SystemTime>,
+    created: Option<SystemTime>,
+    blksize: Option<u64>,
+    blocks: Option<u64>,
+}
+
+impl FileMetadata {
+    /// Metadata for a writable `kind` of file holding `len` bytes, with nothing else known.
+    pub fn new(kind: FileKind, len: u64) -> Self {
+        FileMetadata {
+            len,
+            kind,
+            readonly: false,
+            mode: None,
+            permissions: None,
+            uid: None,
+            gid: None,
+            ino: None,
+            nlink: None,
+            accessed: None,
+            modified: None,
+            changed: None,
+            created: None,
+            blksize: None,
+            blocks: None,
+        }
+    }
+
+    /// Set the permissions, along with the read-only flag and, on unix, the mode.
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.readonly = permissions.readonly();
+        self.mode = mode(&permissions).or(self.mode);
+        self.permissions = Some(permissions);
+        self
+    }
+
+    /// Set the unix mode bits, which also decide whether the file reads as read-only.
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.readonly = mode & 0o222 == 0;
+        self.mode = Some(mode);
+        self.permissions = None;
+        self
+    }
+
+    pub fn with_owner(mut self, uid: u32, gid: u32) -> Self {
+        self.uid = Some(uid);
+        self.gid = Some(gid);
+        self
+    }
+
+    pub fn with_ino(mut self, ino: u64) -> Self {
+        self.ino = Some(ino);
+        self
+    }
+
+    pub fn with_nlink(mut self, nlink: u64) -> Self {
+        self.nlink = Some(nlink);
+        self
+    }
+
+    pub fn with_accessed(mut self, accessed: SystemTime) -> Self {
+        self.accessed = Some(accessed);
+        self
+    }
+
+    pub fn with_modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    pub fn with_changed(mut self, changed: SystemTime) -> Self {
+        self.changed = Some(changed);
+        self
+    }
+
+    pub fn with_created(mut self, created: SystemTime) -> Self {
+        self.created = Some(created);
+        self
+    }
+
+    /// Set the preferred I/O block size, and how many 512 byte blocks the file takes up.
+    pub fn with_blocks(mut self, blksize: u64, blocks: u64) -> Self {
+        self.blksize = Some(blksize);
+        self.blocks = Some(blocks);
+        self
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn kind(&self) -> FileKind {
+        self.kind
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == FileKind::File
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == FileKind::Symlink
+    }
+
+    /// The file's permissions, if the file knows them.
+    pub fn permissions(&self) -> Option<Permissions> {
+        match self.permissions {
+            Some(ref permissions) => Some(permissions.clone()),
+            None => self.mode.and_then(from_mode),
+        }
+    }
+
+    pub fn readonly(&self) -> bool {
+        self.readonly
+    }
+
+    /// The unix mode bits, as `PermissionsExt::mode` reports them.
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    pub fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    pub fn gid(&self) -> Option<u32> {
+        self.gid
+    }
+
+    /// The inode number.
+    pub fn ino(&self) -> Option<u64> {
+        self.ino
+    }
+
+    /// How many hard links point at the file.
+    pub fn nlink(&self) -> Option<u64> {
+        self.nlink
+    }
+
+    /// When the file was last read (atime).
+    pub fn accessed(&self) -> Option<SystemTime> {
+        self.accessed
+    }
+
+    /// When the contents were last changed (mtime).
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// When the contents or metadata were last changed (ctime).
+    pub fn changed(&self) -> Option<SystemTime> {
+        self.changed
+    }
+
+    /// When the file was created (btime).
+    pub fn created(&self) -> Option<SystemTime> {
+        self.created
+    }
+
+    /// The preferred block size for I/O on the file.
+    pub fn blksize(&self) -> Option<u64> {
+        self.blksize
+    }
+
+    /// How many 512 byte blocks the file takes up.
+    pub fn blocks(&self) -> Option<u64> {
+        self.blocks
+    }
+}
+
+/// Everything but the `Permissions`, which are either the same as the mode says or, off unix,
+/// only carry the read-only flag.
+impl PartialEq for FileMetadata {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self.kind == other.kind
+            && self.readonly == other.readonly
+            && self.mode == other.mode
+            && self.uid == other.uid
+            && self.gid == other.gid
+            && self.ino == other.ino
+            && self.nlink == other.nlink
+            && self.accessed == other.accessed
+            && self.modified == other.modified
+            && self.changed == other.changed
+            && self.created == other.created
+            && self.blksize == other.blksize
+            && self.blocks == other.blocks
+    }
+}
+
+impl Eq for FileMetadata {}
+
+impl From<Metadata> for FileMetadata {
+    fn from(metadata: Metadata) -> Self {
+        let file_type = metadata.file_type();
+
+        let kind = if file_type.is_file() {
+            FileKind::File
+        } else if file_type.is_dir() {
+            FileKind::Dir
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else {
+            FileKind::Other
+        };
+
+        let mut file_metadata =
+            FileMetadata::new(kind, metadata.len()).with_permissions(metadata.permissions());
+
+        file_metadata.accessed = metadata.accessed().ok();
+        file_metadata.modified = metadata.modified().ok();
+        file_metadata.created = metadata.created().ok();
+
+        from_os(&mut file_metadata, &metadata);
+        file_metadata
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn mode(permissions: &Permissions) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Some(permissions.mode())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn mode(_: &Permissions) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn from_mode(mode: u32) -> Option<Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Some(Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn from_mode(_: u32) -> Option<Permissions> {
+    None
+}
+
+#[cfg(unix)]
+fn from_os(file_metadata: &mut FileMetadata, metadata: &Metadata) {
+    use std::{os::unix::fs::MetadataExt, time::{Duration, UNIX_EPOCH}};
+
+    let secs = Duration::from_secs(metadata.ctime().unsigned_abs());
+    let nanos = Duration::from_nanos(metadata.ctime_nsec() as u64);
+
+    let changed = if metadata.ctime() < 0 {
+        UNIX_EPOCH.checked_sub(secs)
+    } else {
+        UNIX_EPOCH.checked_add(secs)
+    };
+
+    file_metadata.changed = changed.and_then(|changed| changed.checked_add(nanos));
+
+    file_metadata.uid = Some(metadata.uid());
+    file_metadata.gid = Some(metadata.gid());
+    file_metadata.ino = Some(metadata.ino());
+    file_metadata.nlink = Some(metadata.nlink());
+    file_metadata.blksize = Some(metadata.blksize());
+    file_metadata.blocks = Some(metadata.blocks());
+}
+
+#[cfg(not(unix))]
+fn from_os(_: &mut FileMetadata, _: &Metadata) {}
@@ -0,0 +1,342 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::HashMap,
+    fs::Permissions,
+    io::{Error, SeekFrom},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use futures::{Async, Poll};
+
+use {AsyncFile, FileMetadata, FileOp, LockMode};
+
+/// Somewhere for an `InstrumentedFile` to report its operations, e.g. an adapter feeding an
+/// existing metrics system.
+pub trait FileMetrics: Send + Sync {
+    /// Called as each operation finishes, with how long it took from its first poll and the
+    /// error it failed with, if any.
+    fn record(&self, op: FileOp, elapsed: Duration, error: Option<&Error>);
+}
+
+/// How many buckets a `Histogram` has: one for each power of two microseconds up to about 17
+/// seconds, and one for anything longer.
+const BUCKETS: usize = 26;
+
+/// A histogram of operation latencies, bucketed by powers of two microseconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [u64; BUCKETS],
+    sum: Duration,
+    min: Option<Duration>,
+    max: Option<Duration>,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Histogram {
+            counts: [0; BUCKETS],
+            sum: Duration::from_secs(0),
+            min: None,
+            max: None,
+        }
+    }
+
+    pub fn record(&mut self, elapsed: Duration) {
+        let bucket = (0..BUCKETS - 1)
+            .find(|&i| elapsed <= bound(i))
+            .unwrap_or(BUCKETS - 1);
+
+        self.counts[bucket] += 1;
+        self.sum += elapsed;
+        self.min = Some(self.min.map_or(elapsed, |min| min.min(elapsed)));
+        self.max = Some(self.max.map_or(elapsed, |max| max.max(elapsed)));
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count() {
+            0 => None,
+            n => Some(Duration::from_nanos(
+                (self.sum.as_nanos() / u128::from(n)) as u64,
+            )),
+        }
+    }
+
+    /// Each bucket's upper bound and count, shortest first. The last bucket's bound is
+    /// `Duration::MAX`.
+    pub fn buckets(&self) -> Vec<(Duration, u64)> {
+        (0..BUCKETS).map(|i| (bound(i), self.counts[i])).collect()
+    }
+
+    /// An upper bound on the latency below which `q` of the operations fall, for `q` between 0
+    /// and 1, accurate to within a factor of two.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        let max = self.max?;
+        let rank = ((q * count as f64).ceil() as u64).clamp(1, count);
+
+        let mut seen = 0;
+        for i in 0..BUCKETS {
+            seen += self.counts[i];
+
+            if seen >= rank {
+                return Some(bound(i).min(max));
+            }
+        }
+
+        Some(max)
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram::new()
+    }
+}
+
+fn bound(bucket: usize) -> Duration {
+    if bucket == BUCKETS - 1 {
+        Duration::MAX
+    } else {
+        Duration::from_micros(1 << bucket)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct OpMetrics {
+    calls: u64,
+    errors: u64,
+    latency: Histogram,
+}
+
+/// `FileMetrics` that keeps counters and latency histograms in memory.
+///
+/// Clones share the same metrics, so one can be handed to an `InstrumentedFile` while another
+/// is read from.
+#[derive(Clone, Debug, Default)]
+pub struct MemMetrics {
+    ops: Arc<Mutex<HashMap<FileOp, OpMetrics>>>,
+}
+
+impl MemMetrics {
+    pub fn new() -> Self {
+        MemMetrics::default()
+    }
+
+    /// How many calls of `op` have finished, successfully or not.
+    pub fn calls(&self, op: FileOp) -> u64 {
+        lock(&self.ops).get(&op).map_or(0, |metrics| metrics.calls)
+    }
+
+    /// How many calls of `op` have failed.
+    pub fn errors(&self, op: FileOp) -> u64 {
+        lock(&self.ops).get(&op).map_or(0, |metrics| metrics.errors)
+    }
+
+    /// The latencies of the calls of `op` so far, failed ones included.
+    pub fn latency(&self, op: FileOp) -> Histogram {
+        lock(&self.ops)
+            .get(&op)
+            .map_or_else(Histogram::new, |metrics| metrics.latency.clone())
+    }
+}
+
+impl FileMetrics for MemMetrics {
+    fn record(&self, op: FileOp, elapsed: Duration, error: Option<&Error>) {
+        let mut ops = lock(&self.ops);
+        let metrics = ops.entry(op).or_default();
+
+        metrics.calls += 1;
+        if error.is_some() {
+            metrics.errors += 1;
+        }
+        metrics.latency.record(elapsed);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// An `AsyncFile` that reports how long each operation on the file it wraps takes, and whether
+/// it failed, to a `FileMetrics`.
+///
+/// Operations are timed from their first poll until they finish. One that's abandoned isn't
+/// reported, and the next operation is timed on its own, even if it's the same kind. `try_clone`
+/// resolves to an `InstrumentedFile` reporting to the same metrics.
+#[derive(Debug)]
+pub struct InstrumentedFile<T, M = MemMetrics> {
+    inner: T,
+    metrics: Arc<M>,
+    current: Option<(FileOp, Instant)>,
+}
+
+impl<T, M> InstrumentedFile<T, M>
+where
+    T: AsyncFile,
+    M: FileMetrics,
+{
+    pub fn new(inner: T, metrics: Arc<M>) -> Self {
+        InstrumentedFile {
+            inner,
+            metrics,
+            current: None,
+        }
+    }
+
+    pub fn metrics(&self) -> &Arc<M> {
+        &self.metrics
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn poll_op<R, F>(&mut self, op: FileOp, f: F) -> Poll<R, Error>
+    where
+        F: FnOnce(&mut T) -> Poll<R, Error>,
+    {
+        let started = match self.current {
+            Some((current, started)) if current == op => started,
+            _ => Instant::now(),
+        };
+
+        let res = f(&mut self.inner);
+
+        let error = match res {
+            Ok(Async::NotReady) => {
+                self.current = Some((op, started));
+                return res;
+            }
+            Ok(Async::Ready(_)) => None,
+            Err(ref e) => Some(e),
+        };
+
+        self.current = None;
+        self.metrics.record(op, started.elapsed(), error);
+        res
+    }
+}
+
+impl<T, M> AsyncFile for InstrumentedFile<T, M>
+where
+    T: AsyncFile,
+    M: FileMetrics,
+{
+    type Cloned = InstrumentedFile<T::Cloned, M>;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        self.poll_op(FileOp::Seek, |file| file.poll_seek(pos))
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::SyncAll, |file| file.poll_sync_all())
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::SyncData, |file| file.poll_sync_data())
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        self.poll_op(FileOp::SetLen, |file| file.poll_set_len(size))
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        self.poll_op(FileOp::Metadata, |file| file.poll_metadata())
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<Self::Cloned, Error> {
+        let cloned = try_ready!(self.poll_op(FileOp::TryClone, |file| file.poll_try_clone()));
+
+        Ok(Async::Ready(InstrumentedFile::new(
+            cloned,
+            Arc::clone(&self.metrics),
+        )))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        self.poll_op(FileOp::SetPermissions, |file| {
+            file.poll_set_permissions(perm)
+        })
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        self.poll_op(FileOp::Read, |file| file.poll_read(buf))
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        self.poll_op(FileOp::Write, |file| file.poll_write(buf))
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        self.poll_op(FileOp::ReadAt, |file| file.poll_read_at(buf, offset))
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        self.poll_op(FileOp::WriteAt, |file| file.poll_write_at(buf, offset))
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_op(FileOp::Lock, |file| file.poll_lock(mode))
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_op(FileOp::TryLock, |file| file.poll_try_lock(mode))
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::Unlock, |file| file.poll_unlock())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.inner.path()
+    }
+
+    fn start_operation(&mut self) {
+        self.current = None;
+        self.inner.start_operation()
+    }
+}
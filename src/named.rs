@@ -0,0 +1,256 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    error, fmt,
+    fs::Permissions,
+    io::{Error, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use futures::{Async, Poll};
+
+use {AsyncFile, FileMetadata, LockMode};
+
+/// The error inside the `io::Error` that a `NamedFile` fails with, saying which call on which
+/// file failed, e.g. `set_len(30) on /var/data/x: Permission denied`.
+///
+/// The `io::Error` has the kind of the wrapped file's error, which this holds:
+///
+/// ```rust
+/// # use std::io::{Error, ErrorKind};
+/// use file_futures::PathError;
+///
+/// fn raw_os_error(e: &Error) -> Option<i32> {
+///     e.get_ref()
+///         .and_then(|inner| inner.downcast_ref::<PathError>())
+///         .map_or(e.raw_os_error(), |e| e.error().raw_os_error())
+/// }
+/// # assert_eq!(raw_os_error(&Error::from_raw_os_error(13)), Some(13));
+/// ```
+#[derive(Debug)]
+pub struct PathError {
+    call: String,
+    path: PathBuf,
+    error: Error,
+}
+
+impl PathError {
+    /// The call that failed, with its arguments, e.g. `set_len(30)`.
+    pub fn call(&self) -> &str {
+        &self.call
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The wrapped file's error.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} on {}: {}",
+            self.call,
+            self.path.display(),
+            self.error
+        )
+    }
+}
+
+impl error::Error for PathError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<PathError> for Error {
+    fn from(e: PathError) -> Self {
+        Error::new(e.error.kind(), e)
+    }
+}
+
+/// An `AsyncFile` that knows the path it was opened at, and says so in its errors.
+///
+/// Every error from the wrapped file comes back as a `PathError` inside an `io::Error` of the
+/// same kind. `path` returns the path, so the `tracing` feature's spans name the file too, and
+/// `try_clone` resolves to a `NamedFile` with the same path.
+///
+/// ```rust
+/// # extern crate file_futures;
+/// # extern crate futures;
+/// use file_futures::{AsyncFile, Fault, FaultPlan, FaultyFile, FileOp, MemFile, NamedFile};
+/// use futures::Future;
+/// use std::io::ErrorKind;
+///
+/// # fn main() {
+/// let denied = Fault::Error(ErrorKind::PermissionDenied);
+/// let plan = FaultPlan::new(0).on_call(FileOp::SetLen, 0, denied);
+/// let file = NamedFile::new(FaultyFile::new(MemFile::new(), plan), "/var/data/x");
+///
+/// let err = file.set_len(30).wait().unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+/// assert_eq!(err.error().to_string(), "set_len(30) on /var/data/x: injected fault");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct NamedFile<T> {
+    inner: T,
+    path: PathBuf,
+}
+
+impl<T> NamedFile<T>
+where
+    T: AsyncFile,
+{
+    /// Name `inner` after the path it was opened at.
+    pub fn new<P>(inner: T, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        NamedFile {
+            inner,
+            path: path.into(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn context<R>(&self, res: Poll<R, Error>, call: fmt::Arguments) -> Poll<R, Error> {
+        res.map_err(|error| {
+            PathError {
+                call: call.to_string(),
+                path: self.path.clone(),
+                error,
+            }
+            .into()
+        })
+    }
+}
+
+impl<T> AsyncFile for NamedFile<T>
+where
+    T: AsyncFile,
+{
+    type Cloned = NamedFile<T::Cloned>;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        let res = self.inner.poll_seek(pos);
+        self.context(res, format_args!("seek({:?})", pos))
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        let res = self.inner.poll_sync_all();
+        self.context(res, format_args!("sync_all()"))
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        let res = self.inner.poll_sync_data();
+        self.context(res, format_args!("sync_data()"))
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        let res = self.inner.poll_set_len(size);
+        self.context(res, format_args!("set_len({})", size))
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        let res = self.inner.poll_metadata();
+        self.context(res, format_args!("metadata()"))
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<Self::Cloned, Error> {
+        let res = self.inner.poll_try_clone();
+        let cloned = try_ready!(self.context(res, format_args!("try_clone()")));
+
+        Ok(Async::Ready(NamedFile {
+            inner: cloned,
+            path: self.path.clone(),
+        }))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        let res = self.inner.poll_set_permissions(perm.clone());
+        self.context(res, format_args!("set_permissions({:?})", perm))
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        let len = buf.len();
+        let res = self.inner.poll_read(buf);
+        self.context(res, format_args!("read({})", len))
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        let res = self.inner.poll_write(buf);
+        self.context(res, format_args!("write({})", buf.len()))
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        let len = buf.len();
+        let res = self.inner.poll_read_at(buf, offset);
+        self.context(res, format_args!("read_at({}, {})", len, offset))
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        let res = self.inner.poll_write_at(buf, offset);
+        self.context(res, format_args!("write_at({}, {})", buf.len(), offset))
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        let res = self.inner.poll_lock(mode);
+        self.context(res, format_args!("lock({:?})", mode))
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        let res = self.inner.poll_try_lock(mode);
+        self.context(res, format_args!("try_lock({:?})", mode))
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        let res = self.inner.poll_unlock();
+        self.context(res, format_args!("unlock()"))
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    fn start_operation(&mut self) {
+        self.inner.start_operation()
+    }
+}
@@ -0,0 +1,274 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The machinery shared by files that run their operations on some other thread.
+
+use std::{
+    any::Any,
+    fs::{File as StdFile, Permissions},
+    io::{Error, Read, Result, Seek, SeekFrom, Write},
+    sync::Arc,
+};
+
+use futures::{Async, Poll};
+
+use {blocking, lock, FileMetadata, LockMode};
+
+/// What an operation resolves to, boxed so that one slot can hold any of them.
+pub(crate) type Done = Result<Box<dyn Any + Send>>;
+
+/// Somewhere to run blocking operations.
+pub(crate) trait Spawner {
+    /// A running operation.
+    type Task;
+
+    fn spawn<F>(&self, f: F) -> Self::Task
+    where
+        F: FnOnce() -> Done + Send + 'static;
+
+    /// Like `spawn`, for an operation that may wait on another handle indefinitely, like taking
+    /// a lock. If those run on a fixed set of threads, enough of them waiting at once leaves
+    /// nothing to run the operation they're waiting for.
+    ///
+    /// Spawners whose threads aren't scarce can leave this as `spawn`.
+    fn spawn_waiting<F>(&self, f: F) -> Self::Task
+    where
+        F: FnOnce() -> Done + Send + 'static,
+    {
+        self.spawn(f)
+    }
+
+    /// Poll `task`, notifying the current task once it's finished.
+    fn poll_task(task: &mut Self::Task) -> Poll<Box<dyn Any + Send>, Error>;
+}
+
+/// An operation running elsewhere, along with the arguments it was started with.
+#[derive(Debug, PartialEq)]
+enum Op {
+    Seek(SeekFrom),
+    SyncAll,
+    SyncData,
+    SetLen(u64),
+    Metadata,
+    TryClone,
+    SetPermissions(Permissions),
+    Read(usize),
+    Write(Arc<Vec<u8>>),
+    ReadAt(usize, u64),
+    WriteAt(Arc<Vec<u8>>, u64),
+    Lock(LockMode),
+    TryLock(LockMode),
+    Unlock,
+}
+
+impl Op {
+    /// Whether the operation may wait on another handle, see `Spawner::spawn_waiting`.
+    fn waits(&self) -> bool {
+        matches!(*self, Op::Lock(_))
+    }
+}
+
+/// An operation that's been handed to a `Spawner`.
+#[derive(Debug)]
+struct Pending<T> {
+    op: Op,
+    task: T,
+    /// Set once a new operation has started, so the future that started this one is gone.
+    orphaned: bool,
+}
+
+/// A `std::fs::File` whose operations are run by a `Spawner`.
+///
+/// Only one operation runs at a time. If the future polling one is dropped, the operation still
+/// runs to completion. Polling it again from the same future picks it back up. Any other
+/// operation, including one with the same arguments from a new future, waits for it to finish
+/// and discards its result before starting.
+#[derive(Debug)]
+pub(crate) struct OffloadedFile<S>
+where
+    S: Spawner,
+{
+    std: Arc<StdFile>,
+    spawner: S,
+    pending: Option<Pending<S::Task>>,
+}
+
+impl<S> OffloadedFile<S>
+where
+    S: Spawner,
+{
+    pub(crate) fn new(file: StdFile, spawner: S) -> Self {
+        OffloadedFile {
+            std: Arc::new(file),
+            spawner,
+            pending: None,
+        }
+    }
+
+    pub(crate) fn spawner(&self) -> &S {
+        &self.spawner
+    }
+
+    /// Start `op` unless it's already running, and poll it.
+    fn poll_op<T, F>(&mut self, op: Op, f: F) -> Poll<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&StdFile) -> Result<T> + Send + 'static,
+    {
+        loop {
+            match self.pending {
+                Some(ref pending) if !pending.orphaned && pending.op == op => break,
+                Some(_) => {
+                    // Left behind by a future that was abandoned before it finished.
+                    if let Ok(Async::NotReady) = self.poll_pending() {
+                        return Ok(Async::NotReady);
+                    }
+                }
+                None => {
+                    let file = Arc::clone(&self.std);
+                    let job = move || f(&file).map(|done| Box::new(done) as Box<dyn Any + Send>);
+
+                    let task = if op.waits() {
+                        self.spawner.spawn_waiting(job)
+                    } else {
+                        self.spawner.spawn(job)
+                    };
+
+                    self.pending = Some(Pending {
+                        op,
+                        task,
+                        orphaned: false,
+                    });
+                    break;
+                }
+            }
+        }
+
+        let done = try_ready!(self.poll_pending());
+
+        match done.downcast() {
+            Ok(done) => Ok(Async::Ready(*done)),
+            Err(_) => panic!("Offloaded operation resolved to the wrong type"),
+        }
+    }
+
+    fn poll_pending(&mut self) -> Poll<Box<dyn Any + Send>, Error> {
+        let res = match self.pending {
+            Some(ref mut pending) => S::poll_task(&mut pending.task),
+            None => panic!("No offloaded operation to poll"),
+        };
+
+        if let Ok(Async::NotReady) = res {
+            return Ok(Async::NotReady);
+        }
+
+        self.pending = None;
+        res
+    }
+
+    /// A new operation future was created, so whatever is running now belongs to one that was
+    /// abandoned.
+    pub(crate) fn start_operation(&mut self) {
+        if let Some(ref mut pending) = self.pending {
+            pending.orphaned = true;
+        }
+    }
+
+    pub(crate) fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        self.poll_op(Op::Seek(pos), move |mut file| file.seek(pos))
+    }
+
+    pub(crate) fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        self.poll_op(Op::SyncAll, |file| file.sync_all())
+    }
+
+    pub(crate) fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        self.poll_op(Op::SyncData, |file| file.sync_data())
+    }
+
+    pub(crate) fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        self.poll_op(Op::SetLen(size), move |file| file.set_len(size))
+    }
+
+    pub(crate) fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        self.poll_op(Op::Metadata, |file| file.metadata().map(FileMetadata::from))
+    }
+
+    pub(crate) fn poll_try_clone(&mut self) -> Poll<StdFile, Error> {
+        self.poll_op(Op::TryClone, |file| file.try_clone())
+    }
+
+    pub(crate) fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        let op = Op::SetPermissions(perm.clone());
+
+        self.poll_op(op, move |file| file.set_permissions(perm))
+    }
+
+    pub(crate) fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        let len = buf.len();
+        let read = try_ready!(self.poll_op(Op::Read(len), move |mut file| {
+            let mut read = vec![0; len];
+            let n = file.read(&mut read)?;
+            read.truncate(n);
+            Ok(read)
+        }));
+
+        buf[..read.len()].copy_from_slice(&read);
+        Ok(Async::Ready(read.len()))
+    }
+
+    pub(crate) fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        let data = Arc::new(buf.to_vec());
+        let op = Op::Write(Arc::clone(&data));
+
+        self.poll_op(op, move |mut file| file.write(&data))
+    }
+
+    pub(crate) fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        let len = buf.len();
+        let read = try_ready!(self.poll_op(Op::ReadAt(len, offset), move |file| {
+            let mut read = vec![0; len];
+            let n = blocking::read_at(file, &mut read, offset)?;
+            read.truncate(n);
+            Ok(read)
+        }));
+
+        buf[..read.len()].copy_from_slice(&read);
+        Ok(Async::Ready(read.len()))
+    }
+
+    pub(crate) fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        let data = Arc::new(buf.to_vec());
+        let op = Op::WriteAt(Arc::clone(&data), offset);
+
+        self.poll_op(op, move |file| blocking::write_at(file, &data, offset))
+    }
+
+    pub(crate) fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_op(Op::Lock(mode), move |file| lock::lock(file, mode))
+    }
+
+    pub(crate) fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_op(Op::TryLock(mode), move |file| lock::try_lock(file, mode))
+    }
+
+    pub(crate) fn poll_unlock(&mut self) -> Poll<(), Error> {
+        self.poll_op(Op::Unlock, |file| file.unlock())
+    }
+}
@@ -0,0 +1,85 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::fmt;
+
+/// One of the operations an `AsyncFile` provides, named after its `poll_*` method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileOp {
+    Seek,
+    SyncAll,
+    SyncData,
+    SetLen,
+    Metadata,
+    TryClone,
+    SetPermissions,
+    Read,
+    Write,
+    ReadAt,
+    WriteAt,
+    Lock,
+    TryLock,
+    Unlock,
+}
+
+impl FileOp {
+    /// Every operation, in the order `AsyncFile` declares them.
+    pub const ALL: [FileOp; 14] = [
+        FileOp::Seek,
+        FileOp::SyncAll,
+        FileOp::SyncData,
+        FileOp::SetLen,
+        FileOp::Metadata,
+        FileOp::TryClone,
+        FileOp::SetPermissions,
+        FileOp::Read,
+        FileOp::Write,
+        FileOp::ReadAt,
+        FileOp::WriteAt,
+        FileOp::Lock,
+        FileOp::TryLock,
+        FileOp::Unlock,
+    ];
+
+    /// The operation's name, e.g. `"set_len"`, as `FileError::operation` reports it.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FileOp::Seek => "seek",
+            FileOp::SyncAll => "sync_all",
+            FileOp::SyncData => "sync_data",
+            FileOp::SetLen => "set_len",
+            FileOp::Metadata => "metadata",
+            FileOp::TryClone => "try_clone",
+            FileOp::SetPermissions => "set_permissions",
+            FileOp::Read => "read",
+            FileOp::Write => "write",
+            FileOp::ReadAt => "read_at",
+            FileOp::WriteAt => "write_at",
+            FileOp::Lock => "lock",
+            FileOp::TryLock => "try_lock",
+            FileOp::Unlock => "unlock",
+        }
+    }
+}
+
+impl fmt::Display for FileOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,247 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    any::Any,
+    fmt,
+    fs::{File as StdFile, Permissions},
+    io::{Error, SeekFrom},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+};
+
+use futures::{
+    sync::oneshot::{self, Canceled},
+    Async, Future, Poll,
+};
+
+use offload::{Done, OffloadedFile, Spawner};
+use {AsyncFile, FileMetadata, LockMode};
+
+/// How many threads are in the pool `PooledFile::new` uses.
+const DEFAULT_THREADS: usize = 4;
+
+static DEFAULT_POOL: OnceLock<Pool> = OnceLock::new();
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A small pool of threads for running file operations on.
+///
+/// Cloning a `Pool` gives another handle to the same threads. They exit once every handle, and
+/// every `PooledFile` using them, has been dropped.
+#[derive(Clone)]
+pub struct Pool {
+    jobs: Sender<Job>,
+}
+
+impl Pool {
+    /// Start a pool of `threads` threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is zero, or if the OS refuses to start a thread.
+    pub fn new(threads: usize) -> Self {
+        assert!(threads > 0, "A pool needs at least one thread");
+
+        let (jobs, rx) = mpsc::channel();
+        let rx = Arc::new(Mutex::new(rx));
+
+        for i in 0..threads {
+            let rx = Arc::clone(&rx);
+
+            thread::Builder::new()
+                .name(format!("file-futures-pool-{}", i))
+                .spawn(move || work(&rx))
+                .expect("Failed to start pool thread");
+        }
+
+        Pool { jobs }
+    }
+}
+
+impl fmt::Debug for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pool").finish()
+    }
+}
+
+fn work(jobs: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match jobs.lock() {
+            Ok(jobs) => jobs.recv(),
+            Err(_) => return,
+        };
+
+        match job {
+            // A panicking operation drops its sender, which the file reports as an error.
+            Ok(job) => drop(panic::catch_unwind(AssertUnwindSafe(job))),
+            Err(_) => return,
+        }
+    }
+}
+
+impl Spawner for Pool {
+    type Task = oneshot::Receiver<Done>;
+
+    fn spawn<F>(&self, f: F) -> Self::Task
+    where
+        F: FnOnce() -> Done + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+
+        // If the threads are gone, dropping the job drops `tx`, and polling `rx` reports it.
+        let _ = self.jobs.send(Box::new(move || {
+            let _ = tx.send(f());
+        }));
+
+        rx
+    }
+
+    /// Runs on a thread of its own, so waiting operations can't take up every thread in the pool.
+    fn spawn_waiting<F>(&self, f: F) -> Self::Task
+    where
+        F: FnOnce() -> Done + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+
+        let spawned = thread::Builder::new()
+            .name("file-futures-wait".to_owned())
+            .spawn(move || {
+                let _ = tx.send(f());
+            });
+
+        match spawned {
+            Ok(_) => rx,
+            Err(e) => {
+                let (tx, rx) = oneshot::channel();
+                let _ = tx.send(Err(e));
+                rx
+            }
+        }
+    }
+
+    fn poll_task(task: &mut Self::Task) -> Poll<Box<dyn Any + Send>, Error> {
+        match task.poll() {
+            Ok(Async::Ready(done)) => done.map(Async::Ready),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(Canceled) => Err(Error::other("File operation panicked")),
+        }
+    }
+}
+
+/// A `std::fs::File` whose operations run on a `Pool`.
+///
+/// Unlike `tokio_fs::File`, this doesn't need a tokio runtime: each operation is handed to the
+/// pool, and the task polling it is notified when it's done, so it works on any executor.
+/// Waiting for a lock is the exception: that gets a thread of its own, so files waiting on each
+/// other can't take up the whole pool.
+///
+/// An operation whose future is dropped still finishes in the background; see the crate docs on
+/// cancellation.
+#[derive(Debug)]
+pub struct PooledFile {
+    inner: OffloadedFile<Pool>,
+}
+
+impl PooledFile {
+    /// Wrap `file`, running its operations on a pool of four threads shared by every
+    /// `PooledFile` created this way.
+    pub fn new(file: StdFile) -> Self {
+        let pool = DEFAULT_POOL.get_or_init(|| Pool::new(DEFAULT_THREADS));
+
+        PooledFile::with_pool(file, pool.clone())
+    }
+
+    /// Wrap `file`, running its operations on `pool`.
+    pub fn with_pool(file: StdFile, pool: Pool) -> Self {
+        PooledFile {
+            inner: OffloadedFile::new(file, pool),
+        }
+    }
+}
+
+impl AsyncFile for PooledFile {
+    type Cloned = PooledFile;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        self.inner.poll_seek(pos)
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        self.inner.poll_sync_all()
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        self.inner.poll_sync_data()
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        self.inner.poll_set_len(size)
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        self.inner.poll_metadata()
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<PooledFile, Error> {
+        let file = try_ready!(self.inner.poll_try_clone());
+
+        Ok(Async::Ready(PooledFile::with_pool(file, self.inner.spawner().clone())))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        self.inner.poll_set_permissions(perm)
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        self.inner.poll_read(buf)
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        self.inner.poll_write(buf)
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        self.inner.poll_read_at(buf, offset)
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        self.inner.poll_write_at(buf, offset)
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.inner.poll_lock(mode)
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.inner.poll_try_lock(mode)
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        self.inner.poll_unlock()
+    }
+
+    fn start_operation(&mut self) {
+        self.inner.start_operation()
+    }
+}
@@ -0,0 +1,582 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{cmp, io::{Error, ErrorKind}};
+
+use futures::{Async, Poll};
+
+use driver::Driver;
+use span::OpSpan;
+use {AsyncFile, FileError, Inner, LimitExceeded};
+
+/// How much more room `ReadToEnd` makes in its buffer when it runs out.
+const READ_CHUNK: usize = 8 * 1024;
+
+/// Reads some bytes into `buf`, resolving to the file, the buffer, and how many bytes were read.
+pub struct Read<T, B> {
+    buf: Option<B>,
+    inner: Option<T>,
+    span: OpSpan,
+}
+
+impl<T, B> Read<T, B>
+where
+    B: AsMut<[u8]>,
+{
+    pub(crate) fn new(inner: T, mut buf: B) -> Self {
+        let span = OpSpan::new("read", format_args!("{} bytes", buf.as_mut().len()));
+
+        Read {
+            buf: Some(buf),
+            inner: Some(inner),
+            span,
+        }
+    }
+}
+
+impl<T, B> Read<T, B>
+where
+    B: AsMut<[u8]>,
+{
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, B, usize), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match (self.inner.as_mut(), self.buf.as_mut()) {
+            (Some(inner), Some(buf)) => driver.poll_read(inner, buf.as_mut()),
+            _ => panic!("Polled future after completion"),
+        };
+
+        match res {
+            Ok(Async::Ready(n)) => Ok(Async::Ready((
+                self.inner.take().unwrap(),
+                self.buf.take().unwrap(),
+                n,
+            ))),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => Err(FileError::new("read", e, self.inner.take().unwrap())),
+        }
+    }
+}
+
+/// Writes some bytes from `buf`, resolving to the file, the buffer, and how many bytes were
+/// written.
+pub struct Write<T, B> {
+    buf: Option<B>,
+    inner: Option<T>,
+    span: OpSpan,
+}
+
+impl<T, B> Write<T, B>
+where
+    B: AsRef<[u8]>,
+{
+    pub(crate) fn new(inner: T, buf: B) -> Self {
+        let span = OpSpan::new("write", format_args!("{} bytes", buf.as_ref().len()));
+
+        Write {
+            buf: Some(buf),
+            inner: Some(inner),
+            span,
+        }
+    }
+}
+
+impl<T, B> Write<T, B>
+where
+    B: AsRef<[u8]>,
+{
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, B, usize), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match (self.inner.as_mut(), self.buf.as_ref()) {
+            (Some(inner), Some(buf)) => driver.poll_write(inner, buf.as_ref()),
+            _ => panic!("Polled future after completion"),
+        };
+
+        match res {
+            Ok(Async::Ready(n)) => Ok(Async::Ready((
+                self.inner.take().unwrap(),
+                self.buf.take().unwrap(),
+                n,
+            ))),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => Err(FileError::new("write", e, self.inner.take().unwrap())),
+        }
+    }
+}
+
+/// Fills `buf` completely, failing with `UnexpectedEof` if the file ends first.
+///
+/// Resolves to the file, the buffer, and the buffer's length.
+pub struct ReadExact<T, B> {
+    pos: usize,
+    buf: Option<B>,
+    inner: Option<T>,
+    span: OpSpan,
+}
+
+impl<T, B> ReadExact<T, B>
+where
+    B: AsMut<[u8]>,
+{
+    pub(crate) fn new(inner: T, mut buf: B) -> Self {
+        let span = OpSpan::new("read_exact", format_args!("{} bytes", buf.as_mut().len()));
+
+        ReadExact {
+            pos: 0,
+            buf: Some(buf),
+            inner: Some(inner),
+            span,
+        }
+    }
+}
+
+impl<T, B> ReadExact<T, B>
+where
+    B: AsMut<[u8]>,
+{
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, B, usize), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        loop {
+            let res = match (self.inner.as_mut(), self.buf.as_mut()) {
+                (Some(inner), Some(buf)) => {
+                    let buf = buf.as_mut();
+
+                    if self.pos == buf.len() {
+                        Ok(Async::Ready(None))
+                    } else {
+                        driver.poll_read(inner, &mut buf[self.pos..]).map(|ready| ready.map(Some))
+                    }
+                }
+                _ => panic!("Polled future after completion"),
+            };
+
+            match res {
+                Ok(Async::Ready(Some(0))) => {
+                    let e = Error::new(ErrorKind::UnexpectedEof, "early eof");
+
+                    return Err(FileError::new("read_exact", e, self.inner.take().unwrap()));
+                }
+                Ok(Async::Ready(Some(n))) => self.pos += n,
+                Ok(Async::Ready(None)) => {
+                    return Ok(Async::Ready((
+                        self.inner.take().unwrap(),
+                        self.buf.take().unwrap(),
+                        self.pos,
+                    )))
+                }
+                Ok(_) => return Ok(Async::NotReady),
+                Err(e) => return Err(FileError::new("read_exact", e, self.inner.take().unwrap())),
+            }
+        }
+    }
+}
+
+/// Writes all of `buf`, failing with `WriteZero` if the file stops accepting bytes.
+///
+/// Resolves to the file, the buffer, and the buffer's length.
+pub struct WriteAll<T, B> {
+    pos: usize,
+    buf: Option<B>,
+    inner: Option<T>,
+    span: OpSpan,
+}
+
+impl<T, B> WriteAll<T, B>
+where
+    B: AsRef<[u8]>,
+{
+    pub(crate) fn new(inner: T, buf: B) -> Self {
+        let span = OpSpan::new("write_all", format_args!("{} bytes", buf.as_ref().len()));
+
+        WriteAll {
+            pos: 0,
+            buf: Some(buf),
+            inner: Some(inner),
+            span,
+        }
+    }
+}
+
+impl<T, B> WriteAll<T, B>
+where
+    B: AsRef<[u8]>,
+{
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, B, usize), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        loop {
+            let res = match (self.inner.as_mut(), self.buf.as_ref()) {
+                (Some(inner), Some(buf)) => {
+                    let buf = buf.as_ref();
+
+                    if self.pos == buf.len() {
+                        Ok(Async::Ready(None))
+                    } else {
+                        driver.poll_write(inner, &buf[self.pos..]).map(|ready| ready.map(Some))
+                    }
+                }
+                _ => panic!("Polled future after completion"),
+            };
+
+            match res {
+                Ok(Async::Ready(Some(0))) => {
+                    let e = Error::new(ErrorKind::WriteZero, "failed to write whole buffer");
+
+                    return Err(FileError::new("write_all", e, self.inner.take().unwrap()));
+                }
+                Ok(Async::Ready(Some(n))) => self.pos += n,
+                Ok(Async::Ready(None)) => {
+                    return Ok(Async::Ready((
+                        self.inner.take().unwrap(),
+                        self.buf.take().unwrap(),
+                        self.pos,
+                    )))
+                }
+                Ok(_) => return Ok(Async::NotReady),
+                Err(e) => return Err(FileError::new("write_all", e, self.inner.take().unwrap())),
+            }
+        }
+    }
+}
+
+/// Reads some bytes into `buf` from a fixed offset, leaving the file's cursor alone.
+///
+/// Resolves to the file, the buffer, and how many bytes were read.
+pub struct ReadAt<T, B> {
+    offset: u64,
+    buf: Option<B>,
+    inner: Option<T>,
+    span: OpSpan,
+}
+
+impl<T, B> ReadAt<T, B>
+where
+    B: AsMut<[u8]>,
+{
+    pub(crate) fn new(inner: T, offset: u64, mut buf: B) -> Self {
+        let len = buf.as_mut().len();
+        let span = OpSpan::new("read_at", format_args!("{} bytes at {}", len, offset));
+
+        ReadAt {
+            offset,
+            buf: Some(buf),
+            inner: Some(inner),
+            span,
+        }
+    }
+}
+
+impl<T, B> ReadAt<T, B>
+where
+    B: AsMut<[u8]>,
+{
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, B, usize), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match (self.inner.as_mut(), self.buf.as_mut()) {
+            (Some(inner), Some(buf)) => driver.poll_read_at(inner, buf.as_mut(), self.offset),
+            _ => panic!("Polled future after completion"),
+        };
+
+        match res {
+            Ok(Async::Ready(n)) => Ok(Async::Ready((
+                self.inner.take().unwrap(),
+                self.buf.take().unwrap(),
+                n,
+            ))),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => Err(FileError::new("read_at", e, self.inner.take().unwrap())),
+        }
+    }
+}
+
+/// Writes some bytes from `buf` at a fixed offset, leaving the file's cursor alone.
+///
+/// Resolves to the file, the buffer, and how many bytes were written.
+pub struct WriteAt<T, B> {
+    offset: u64,
+    buf: Option<B>,
+    inner: Option<T>,
+    span: OpSpan,
+}
+
+impl<T, B> WriteAt<T, B>
+where
+    B: AsRef<[u8]>,
+{
+    pub(crate) fn new(inner: T, offset: u64, buf: B) -> Self {
+        let len = buf.as_ref().len();
+        let span = OpSpan::new("write_at", format_args!("{} bytes at {}", len, offset));
+
+        WriteAt {
+            offset,
+            buf: Some(buf),
+            inner: Some(inner),
+            span,
+        }
+    }
+}
+
+impl<T, B> WriteAt<T, B>
+where
+    B: AsRef<[u8]>,
+{
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, B, usize), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let res = match (self.inner.as_mut(), self.buf.as_ref()) {
+            (Some(inner), Some(buf)) => driver.poll_write_at(inner, buf.as_ref(), self.offset),
+            _ => panic!("Polled future after completion"),
+        };
+
+        match res {
+            Ok(Async::Ready(n)) => Ok(Async::Ready((
+                self.inner.take().unwrap(),
+                self.buf.take().unwrap(),
+                n,
+            ))),
+            Ok(_) => Ok(Async::NotReady),
+            Err(e) => Err(FileError::new("write_at", e, self.inner.take().unwrap())),
+        }
+    }
+}
+
+/// Reads the rest of a file, from the cursor on, into a `Vec<u8>`, failing with `LimitExceeded`
+/// if more than `limit` bytes are left.
+///
+/// The buffer is sized up front from the file's metadata, but never past the limit. The limit
+/// only applies to the bytes actually read.
+pub struct ReadToEnd<T> {
+    limit: u64,
+    sized: bool,
+    buf: Vec<u8>,
+    inner: Option<T>,
+    span: OpSpan,
+}
+
+impl<T> ReadToEnd<T> {
+    pub(crate) fn new(inner: T, limit: u64) -> Self {
+        ReadToEnd {
+            limit,
+            sized: false,
+            buf: Vec::new(),
+            inner: Some(inner),
+            span: OpSpan::new("read_to_end", format_args!("limit {}", limit)),
+        }
+    }
+
+    /// Make progress on the read, resolving to `true` once the file is exhausted.
+    fn poll_step<D>(&mut self, driver: &mut D) -> Poll<bool, Error>
+    where
+        D: Driver<T>,
+    {
+        let inner = match self.inner {
+            Some(ref mut inner) => inner,
+            None => panic!("Polled future after completion"),
+        };
+
+        if !self.sized {
+            // Only a hint: the cursor may be anywhere, and the file may change as it's read.
+            let len = try_ready!(driver.poll_metadata(inner)).len();
+            let hint = cmp::min(len, self.limit.saturating_add(1));
+
+            self.buf.reserve(cmp::min(hint, usize::MAX as u64) as usize);
+            self.sized = true;
+        }
+
+        let len = self.buf.len();
+
+        if self.buf.capacity() == len {
+            self.buf.reserve(READ_CHUNK);
+        }
+
+        // Read at most one byte past the limit, which is enough to tell the file is too large.
+        let allowed = cmp::min(self.limit.saturating_add(1) - len as u64, usize::MAX as u64);
+        let want = cmp::min(self.buf.capacity() - len, allowed as usize);
+
+        self.buf.resize(len + want, 0);
+        let res = driver.poll_read(inner, &mut self.buf[len..]);
+
+        let n = match res {
+            Ok(Async::Ready(n)) => n,
+            Ok(Async::NotReady) => {
+                self.buf.truncate(len);
+                return Ok(Async::NotReady);
+            }
+            Err(e) => {
+                self.buf.truncate(len);
+                return Err(e);
+            }
+        };
+
+        self.buf.truncate(len + n);
+
+        if self.buf.len() as u64 > self.limit {
+            return Err(LimitExceeded::new(self.limit).into());
+        }
+
+        Ok(Async::Ready(n == 0))
+    }
+
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, Vec<u8>), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        loop {
+            match self.poll_step(driver) {
+                Ok(Async::Ready(true)) => {
+                    let buf = ::std::mem::take(&mut self.buf);
+
+                    return Ok(Async::Ready((self.inner.take().unwrap(), buf)));
+                }
+                Ok(Async::Ready(false)) => (),
+                Ok(_) => return Ok(Async::NotReady),
+                Err(e) => return Err(FileError::new("read_to_end", e, self.inner.take().unwrap())),
+            }
+        }
+    }
+}
+
+/// Reads the rest of a file, from the cursor on, into a `String`, failing with `LimitExceeded` if
+/// more than `limit` bytes are left, or with `InvalidData` if it isn't UTF-8.
+pub struct ReadToString<T> {
+    inner: ReadToEnd<T>,
+    span: OpSpan,
+}
+
+impl<T> ReadToString<T> {
+    pub(crate) fn new(inner: T, limit: u64) -> Self {
+        ReadToString {
+            inner: ReadToEnd::new(inner, limit),
+            span: OpSpan::new("read_to_string", format_args!("limit {}", limit)),
+        }
+    }
+
+    /// The file the operation runs on.
+    ///
+    /// # Panics
+    ///
+    /// If the future has already resolved.
+    pub fn get_ref(&self) -> &T {
+        self.inner.get_ref()
+    }
+
+    /// The file the operation runs on.
+    ///
+    /// # Panics
+    ///
+    /// If the future has already resolved.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
+    /// Abandon the operation, handing back the file. See the crate docs on cancellation for the
+    /// state the file is left in.
+    ///
+    /// # Panics
+    ///
+    /// If the future has already resolved.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+
+    pub(crate) fn file(&self) -> Option<&T> {
+        self.inner.file()
+    }
+
+    fn poll_with<D>(&mut self, driver: &mut D) -> Poll<(T, String), FileError<T>>
+    where
+        D: Driver<T>,
+    {
+        let (file, buf) = match self.inner.poll_with(driver) {
+            Ok(Async::Ready(read)) => read,
+            Ok(_) => return Ok(Async::NotReady),
+            Err(e) => {
+                let (file, e) = e.into_parts();
+
+                return Err(FileError::new("read_to_string", e, file));
+            }
+        };
+
+        match String::from_utf8(buf) {
+            Ok(string) => Ok(Async::Ready((file, string))),
+            Err(e) => Err(FileError::new(
+                "read_to_string",
+                Error::new(ErrorKind::InvalidData, e),
+                file,
+            )),
+        }
+    }
+}
+
+impl<T> Inner for ReadToString<T>
+where
+    T: AsyncFile,
+{
+    type File = T;
+
+    fn inner_ref(&self) -> &T {
+        self.inner.inner_ref()
+    }
+
+    fn inner_mut(&mut self) -> &mut T {
+        self.inner.inner_mut()
+    }
+}
+
+delegate_async_file!(impl ReadToString<T>);
+
+delegate_async_file!(
+    Read<T, B>,
+    Write<T, B>,
+    ReadExact<T, B>,
+    WriteAll<T, B>,
+    ReadAt<T, B>,
+    WriteAt<T, B>,
+    ReadToEnd<T>,
+);
+
+file_accessors!(
+    Read<T, B>,
+    Write<T, B>,
+    ReadExact<T, B>,
+    WriteAll<T, B>,
+    ReadAt<T, B>,
+    WriteAt<T, B>,
+    ReadToEnd<T>,
+);
+
+operation_future!(
+    Read<T, B: AsMut<[u8]>> => (T, B, usize),
+    Write<T, B: AsRef<[u8]>> => (T, B, usize),
+    ReadExact<T, B: AsMut<[u8]>> => (T, B, usize),
+    WriteAll<T, B: AsRef<[u8]>> => (T, B, usize),
+    ReadAt<T, B: AsMut<[u8]>> => (T, B, usize),
+    WriteAt<T, B: AsRef<[u8]>> => (T, B, usize),
+    ReadToEnd<T> => (T, Vec<u8>),
+    ReadToString<T> => (T, String),
+);
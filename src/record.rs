@@ -0,0 +1,574 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::VecDeque,
+    fs::Permissions,
+    io::{Error, ErrorKind, SeekFrom},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use futures::{Async, Poll};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use metadata;
+use {AsyncFile, FileMetadata, FileOp, LockMode};
+
+/// A call made on a `RecordingFile`, with the arguments that matter for replaying it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RecordedCall {
+    Seek(#[cfg_attr(feature = "serde", serde(with = "SeekFromDef"))] SeekFrom),
+    SyncAll,
+    SyncData,
+    SetLen(u64),
+    Metadata,
+    TryClone,
+    SetPermissions { readonly: bool, mode: Option<u32> },
+    /// Read into a buffer of this length.
+    Read(usize),
+    /// Write from a buffer of this length.
+    Write(usize),
+    /// Read into a buffer of this length, from this offset.
+    ReadAt(usize, u64),
+    /// Write from a buffer of this length, at this offset.
+    WriteAt(usize, u64),
+    Lock(LockMode),
+    TryLock(LockMode),
+    Unlock,
+}
+
+impl RecordedCall {
+    pub fn op(&self) -> FileOp {
+        match *self {
+            RecordedCall::Seek(_) => FileOp::Seek,
+            RecordedCall::SyncAll => FileOp::SyncAll,
+            RecordedCall::SyncData => FileOp::SyncData,
+            RecordedCall::SetLen(_) => FileOp::SetLen,
+            RecordedCall::Metadata => FileOp::Metadata,
+            RecordedCall::TryClone => FileOp::TryClone,
+            RecordedCall::SetPermissions { .. } => FileOp::SetPermissions,
+            RecordedCall::Read(_) => FileOp::Read,
+            RecordedCall::Write(_) => FileOp::Write,
+            RecordedCall::ReadAt(..) => FileOp::ReadAt,
+            RecordedCall::WriteAt(..) => FileOp::WriteAt,
+            RecordedCall::Lock(_) => FileOp::Lock,
+            RecordedCall::TryLock(_) => FileOp::TryLock,
+            RecordedCall::Unlock => FileOp::Unlock,
+        }
+    }
+
+    fn set_permissions(perm: &Permissions) -> Self {
+        RecordedCall::SetPermissions {
+            readonly: perm.readonly(),
+            mode: metadata::mode(perm),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SeekFrom")]
+enum SeekFromDef {
+    Start(u64),
+    End(i64),
+    Current(i64),
+}
+
+/// What a successful call resolved to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RecordedValue {
+    /// Nothing, or a file handle that can't be recorded.
+    Unit,
+    /// The position a seek ended up at.
+    Position(u64),
+    /// How many bytes were written.
+    Written(usize),
+    /// The bytes that were read.
+    Data(Vec<u8>),
+    Metadata(FileMetadata),
+}
+
+/// A failed call's error, as far as it can be recorded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecordedError {
+    #[cfg_attr(feature = "serde", serde(with = "error_kind"))]
+    pub kind: ErrorKind,
+    pub raw_os_error: Option<i32>,
+    pub message: String,
+}
+
+impl RecordedError {
+    /// Build an error like the one that was recorded.
+    pub fn to_error(&self) -> Error {
+        match self.raw_os_error {
+            Some(errno) => Error::from_raw_os_error(errno),
+            None => Error::new(self.kind, self.message.clone()),
+        }
+    }
+}
+
+impl<'a> From<&'a Error> for RecordedError {
+    fn from(e: &'a Error) -> Self {
+        RecordedError {
+            kind: stable(e.kind()),
+            raw_os_error: e.raw_os_error(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// The stable kinds, which are all a `RecordedError` keeps; the rest are recorded as `Other`.
+const KINDS: &[ErrorKind] = &[
+    ErrorKind::NotFound,
+    ErrorKind::PermissionDenied,
+    ErrorKind::ConnectionRefused,
+    ErrorKind::ConnectionReset,
+    ErrorKind::HostUnreachable,
+    ErrorKind::NetworkUnreachable,
+    ErrorKind::ConnectionAborted,
+    ErrorKind::NotConnected,
+    ErrorKind::AddrInUse,
+    ErrorKind::AddrNotAvailable,
+    ErrorKind::NetworkDown,
+    ErrorKind::BrokenPipe,
+    ErrorKind::AlreadyExists,
+    ErrorKind::WouldBlock,
+    ErrorKind::NotADirectory,
+    ErrorKind::IsADirectory,
+    ErrorKind::DirectoryNotEmpty,
+    ErrorKind::ReadOnlyFilesystem,
+    ErrorKind::StaleNetworkFileHandle,
+    ErrorKind::InvalidInput,
+    ErrorKind::InvalidData,
+    ErrorKind::TimedOut,
+    ErrorKind::WriteZero,
+    ErrorKind::StorageFull,
+    ErrorKind::NotSeekable,
+    ErrorKind::QuotaExceeded,
+    ErrorKind::FileTooLarge,
+    ErrorKind::ResourceBusy,
+    ErrorKind::ExecutableFileBusy,
+    ErrorKind::Deadlock,
+    ErrorKind::CrossesDevices,
+    ErrorKind::TooManyLinks,
+    ErrorKind::InvalidFilename,
+    ErrorKind::ArgumentListTooLong,
+    ErrorKind::Interrupted,
+    ErrorKind::Unsupported,
+    ErrorKind::UnexpectedEof,
+    ErrorKind::OutOfMemory,
+    ErrorKind::Other,
+];
+
+fn stable(kind: ErrorKind) -> ErrorKind {
+    if KINDS.contains(&kind) {
+        kind
+    } else {
+        ErrorKind::Other
+    }
+}
+
+/// `ErrorKind` isn't serializable, so it's written as its name.
+#[cfg(feature = "serde")]
+mod error_kind {
+    use std::io::ErrorKind;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::KINDS;
+
+    pub fn serialize<S>(kind: &ErrorKind, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&format_args!("{:?}", kind))
+    }
+
+    /// Kinds this build doesn't know the name of come back as `Other`.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<ErrorKind, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+
+        Ok(KINDS
+            .iter()
+            .cloned()
+            .find(|kind| format!("{:?}", kind) == name)
+            .unwrap_or(ErrorKind::Other))
+    }
+}
+
+/// One call in a trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TraceEvent {
+    pub call: RecordedCall,
+    pub result: Result<RecordedValue, RecordedError>,
+    /// How long the call took, from its first poll to its last.
+    pub duration: Duration,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// An `AsyncFile` that records every call made on the file it wraps.
+///
+/// Each call is recorded once it completes, along with what it resolved to and how long it
+/// took, so the trace can be inspected, serialized with the `serde` feature, or played back
+/// through a `ReplayFile`. Reads record the bytes they read; writes only record how many bytes
+/// they were given and how many were written. `try_clone` resolves to a `RecordingFile` adding
+/// to the same trace.
+#[derive(Debug)]
+pub struct RecordingFile<T> {
+    inner: T,
+    trace: Arc<Mutex<Vec<TraceEvent>>>,
+    current: Option<(FileOp, Instant)>,
+}
+
+impl<T> RecordingFile<T>
+where
+    T: AsyncFile,
+{
+    pub fn new(inner: T) -> Self {
+        RecordingFile {
+            inner,
+            trace: Arc::new(Mutex::new(Vec::new())),
+            current: None,
+        }
+    }
+
+    /// The calls recorded so far, oldest first.
+    pub fn trace(&self) -> Vec<TraceEvent> {
+        lock(&self.trace).clone()
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Note the start of a call, unless it's the one already in flight.
+    fn begin(&mut self, op: FileOp) {
+        match self.current {
+            Some((current, _)) if current == op => (),
+            _ => self.current = Some((op, Instant::now())),
+        }
+    }
+
+    fn record<R, V>(&mut self, call: RecordedCall, res: &Poll<R, Error>, value: V)
+    where
+        V: FnOnce(&R) -> RecordedValue,
+    {
+        let result = match *res {
+            Ok(Async::Ready(ref ready)) => Ok(value(ready)),
+            Ok(Async::NotReady) => return,
+            Err(ref e) => Err(RecordedError::from(e)),
+        };
+
+        let duration = match self.current.take() {
+            Some((_, started)) => started.elapsed(),
+            None => Duration::from_secs(0),
+        };
+
+        lock(&self.trace).push(TraceEvent {
+            call,
+            result,
+            duration,
+        });
+    }
+
+    fn poll_unit<F>(&mut self, call: RecordedCall, f: F) -> Poll<(), Error>
+    where
+        F: FnOnce(&mut T) -> Poll<(), Error>,
+    {
+        self.begin(call.op());
+        let res = f(&mut self.inner);
+        self.record(call, &res, |_| RecordedValue::Unit);
+        res
+    }
+}
+
+impl<T> AsyncFile for RecordingFile<T>
+where
+    T: AsyncFile,
+{
+    type Cloned = RecordingFile<T::Cloned>;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        self.begin(FileOp::Seek);
+        let res = self.inner.poll_seek(pos);
+        self.record(RecordedCall::Seek(pos), &res, |&pos| RecordedValue::Position(pos));
+        res
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        self.poll_unit(RecordedCall::SyncAll, T::poll_sync_all)
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        self.poll_unit(RecordedCall::SyncData, T::poll_sync_data)
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        self.poll_unit(RecordedCall::SetLen(size), |file| file.poll_set_len(size))
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        self.begin(FileOp::Metadata);
+        let res = self.inner.poll_metadata();
+        self.record(RecordedCall::Metadata, &res, |metadata| {
+            RecordedValue::Metadata(metadata.clone())
+        });
+        res
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<Self::Cloned, Error> {
+        self.begin(FileOp::TryClone);
+        let res = self.inner.poll_try_clone();
+        self.record(RecordedCall::TryClone, &res, |_| RecordedValue::Unit);
+
+        Ok(Async::Ready(RecordingFile {
+            inner: try_ready!(res),
+            trace: Arc::clone(&self.trace),
+            current: None,
+        }))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        let call = RecordedCall::set_permissions(&perm);
+
+        self.poll_unit(call, |file| file.poll_set_permissions(perm))
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        self.begin(FileOp::Read);
+        let res = self.inner.poll_read(buf);
+        let call = RecordedCall::Read(buf.len());
+        self.record(call, &res, |&n| RecordedValue::Data(buf[..n].to_vec()));
+        res
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        self.begin(FileOp::Write);
+        let res = self.inner.poll_write(buf);
+        self.record(RecordedCall::Write(buf.len()), &res, |&n| RecordedValue::Written(n));
+        res
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        self.begin(FileOp::ReadAt);
+        let res = self.inner.poll_read_at(buf, offset);
+        let call = RecordedCall::ReadAt(buf.len(), offset);
+        self.record(call, &res, |&n| RecordedValue::Data(buf[..n].to_vec()));
+        res
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        self.begin(FileOp::WriteAt);
+        let res = self.inner.poll_write_at(buf, offset);
+        let call = RecordedCall::WriteAt(buf.len(), offset);
+        self.record(call, &res, |&n| RecordedValue::Written(n));
+        res
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_unit(RecordedCall::Lock(mode), |file| file.poll_lock(mode))
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_unit(RecordedCall::TryLock(mode), |file| file.poll_try_lock(mode))
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        self.poll_unit(RecordedCall::Unlock, T::poll_unlock)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.inner.path()
+    }
+
+    fn start_operation(&mut self) {
+        self.current = None;
+        self.inner.start_operation()
+    }
+}
+
+/// An `AsyncFile` that plays back a trace recorded by a `RecordingFile`.
+///
+/// Each call must match the next one in the trace, and resolves to whatever that one did,
+/// straight away: reads fill the buffer with the recorded bytes, and failed calls fail with an
+/// error of the same kind and OS error code. A call that doesn't match fails with
+/// `InvalidInput` and leaves the trace where it was; once the trace runs out, every call fails
+/// with `UnexpectedEof`.
+///
+/// `try_clone` resolves to a `ReplayFile` playing back the same trace, so calls made through
+/// either handle have to come in the recorded order.
+#[derive(Clone, Debug)]
+pub struct ReplayFile {
+    trace: Arc<Mutex<VecDeque<TraceEvent>>>,
+}
+
+impl ReplayFile {
+    pub fn new<I>(trace: I) -> Self
+    where
+        I: IntoIterator<Item = TraceEvent>,
+    {
+        ReplayFile {
+            trace: Arc::new(Mutex::new(trace.into_iter().collect())),
+        }
+    }
+
+    /// How many calls are left in the trace.
+    pub fn remaining(&self) -> usize {
+        lock(&self.trace).len()
+    }
+
+    fn next(&mut self, call: RecordedCall) -> Result<RecordedValue, Error> {
+        let mut trace = lock(&self.trace);
+
+        let matches = match trace.front() {
+            Some(event) => event.call == call,
+            None => {
+                let message = format!("trace has run out, but got {:?}", call);
+                return Err(Error::new(ErrorKind::UnexpectedEof, message));
+            }
+        };
+
+        if !matches {
+            let expected = &trace.front().unwrap().call;
+            let message = format!("trace expected {:?}, but got {:?}", expected, call);
+            return Err(Error::new(ErrorKind::InvalidInput, message));
+        }
+
+        match trace.pop_front().unwrap().result {
+            Ok(value) => Ok(value),
+            Err(e) => Err(e.to_error()),
+        }
+    }
+
+    fn next_unit(&mut self, call: RecordedCall) -> Poll<(), Error> {
+        self.next(call).map(|_| Async::Ready(()))
+    }
+
+    fn next_read(&mut self, call: RecordedCall, buf: &mut [u8]) -> Poll<usize, Error> {
+        match self.next(call)? {
+            RecordedValue::Data(ref data) if data.len() <= buf.len() => {
+                buf[..data.len()].copy_from_slice(data);
+                Ok(Async::Ready(data.len()))
+            }
+            value => Err(unexpected(&value)),
+        }
+    }
+
+    fn next_written(&mut self, call: RecordedCall) -> Poll<usize, Error> {
+        match self.next(call)? {
+            RecordedValue::Written(n) => Ok(Async::Ready(n)),
+            value => Err(unexpected(&value)),
+        }
+    }
+}
+
+fn unexpected(value: &RecordedValue) -> Error {
+    let message = format!("trace has {:?}, which this call can't resolve to", value);
+
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+impl AsyncFile for ReplayFile {
+    type Cloned = ReplayFile;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        match self.next(RecordedCall::Seek(pos))? {
+            RecordedValue::Position(pos) => Ok(Async::Ready(pos)),
+            value => Err(unexpected(&value)),
+        }
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        self.next_unit(RecordedCall::SyncAll)
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        self.next_unit(RecordedCall::SyncData)
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        self.next_unit(RecordedCall::SetLen(size))
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        match self.next(RecordedCall::Metadata)? {
+            RecordedValue::Metadata(metadata) => Ok(Async::Ready(metadata)),
+            value => Err(unexpected(&value)),
+        }
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<ReplayFile, Error> {
+        self.next(RecordedCall::TryClone)?;
+
+        Ok(Async::Ready(self.clone()))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        self.next_unit(RecordedCall::set_permissions(&perm))
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        self.next_read(RecordedCall::Read(buf.len()), buf)
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        self.next_written(RecordedCall::Write(buf.len()))
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        self.next_read(RecordedCall::ReadAt(buf.len(), offset), buf)
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        self.next_written(RecordedCall::WriteAt(buf.len(), offset))
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.next_unit(RecordedCall::Lock(mode))
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.next_unit(RecordedCall::TryLock(mode))
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        self.next_unit(RecordedCall::Unlock)
+    }
+}
@@ -0,0 +1,370 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    error, fmt,
+    fs::Permissions,
+    io::{Error, ErrorKind, SeekFrom},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use futures::{Async, Poll};
+
+use timer::{self, Wakeup};
+use {AsyncFile, FileMetadata, FileOp, LockMode};
+
+/// Which errors a `RetryingFile` retries, how often, and how long it waits in between.
+///
+/// The wait doubles after each retry, from the initial backoff up to the maximum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    kinds: Vec<ErrorKind>,
+    errnos: Vec<i32>,
+}
+
+impl RetryPolicy {
+    /// A policy that makes up to `max_attempts` attempts at each operation, backing off from
+    /// 10ms to 1s, but treats no errors as transient yet.
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            kinds: Vec::new(),
+            errnos: Vec::new(),
+        }
+    }
+
+    /// Make up to `max_attempts` attempts at each operation, counting the first.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Wait `initial` before the first retry, doubling the wait each time up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Retry errors of this kind.
+    pub fn retry_kind(mut self, kind: ErrorKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// Retry errors with this raw OS error code.
+    pub fn retry_errno(mut self, errno: i32) -> Self {
+        self.errnos.push(errno);
+        self
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Whether `e` is worth another attempt.
+    pub fn is_transient(&self, e: &Error) -> bool {
+        self.kinds.contains(&e.kind())
+            || e.raw_os_error()
+                .is_some_and(|errno| self.errnos.contains(&errno))
+    }
+
+    /// How long to wait before the `retry`th retry, counting from one.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+
+        self.backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+}
+
+/// Three attempts, backing off from 10ms to 1s, retrying `Interrupted`, `WouldBlock` and
+/// `StaleNetworkFileHandle`, which is what `EINTR`, `EAGAIN` and `ESTALE` come back as.
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(3)
+            .retry_kind(ErrorKind::Interrupted)
+            .retry_kind(ErrorKind::WouldBlock)
+            .retry_kind(ErrorKind::StaleNetworkFileHandle)
+    }
+}
+
+/// The error inside the `io::Error` that a `RetryingFile` fails with when an operation still
+/// fails after being retried.
+///
+/// The `io::Error` has the kind of the last attempt's error, which this holds along with the
+/// number of attempts made:
+///
+/// ```rust
+/// # use std::io::{Error, ErrorKind};
+/// use file_futures::RetriesExhausted;
+///
+/// fn attempts(e: &Error) -> u32 {
+///     e.get_ref()
+///         .and_then(|inner| inner.downcast_ref::<RetriesExhausted>())
+///         .map_or(1, RetriesExhausted::attempts)
+/// }
+/// # assert_eq!(attempts(&Error::new(ErrorKind::Other, "nope")), 1);
+/// ```
+#[derive(Debug)]
+pub struct RetriesExhausted {
+    attempts: u32,
+    error: Error,
+}
+
+impl RetriesExhausted {
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// The last attempt's error.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl fmt::Display for RetriesExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed after {} attempts: {}", self.attempts, self.error)
+    }
+}
+
+impl error::Error for RetriesExhausted {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<RetriesExhausted> for Error {
+    fn from(e: RetriesExhausted) -> Self {
+        Error::new(e.error.kind(), e)
+    }
+}
+
+/// The operation in flight, and how far through its attempts it is.
+#[derive(Debug)]
+struct Current {
+    op: FileOp,
+    attempt: u32,
+    retry_at: Option<Instant>,
+    /// Wakes the task once the backoff is over.
+    wakeup: Option<Wakeup>,
+}
+
+/// An `AsyncFile` that retries operations on the file it wraps when they fail with an error
+/// its `RetryPolicy` counts as transient.
+///
+/// Between attempts the task is put to sleep, and a timer thread shared by the whole process
+/// wakes it once the backoff is over. An operation that fails after being retried fails with a
+/// `RetriesExhausted`, which says how many attempts were made; errors on the first attempt come
+/// back as they are.
+///
+/// `try_lock` failing with `WouldBlock` means the lock is held elsewhere, so that's never
+/// retried.
+#[derive(Debug)]
+pub struct RetryingFile<T> {
+    inner: T,
+    policy: RetryPolicy,
+    current: Option<Current>,
+}
+
+impl<T> RetryingFile<T>
+where
+    T: AsyncFile,
+{
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        RetryingFile {
+            inner,
+            policy,
+            current: None,
+        }
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn poll_op<R, F>(&mut self, op: FileOp, mut f: F) -> Poll<R, Error>
+    where
+        F: FnMut(&mut T) -> Poll<R, Error>,
+    {
+        let restart = match self.current {
+            Some(ref current) => current.op != op,
+            None => true,
+        };
+
+        if restart {
+            self.current = Some(Current {
+                op,
+                attempt: 1,
+                retry_at: None,
+                wakeup: None,
+            });
+        }
+
+        loop {
+            let attempt = {
+                let current = self.current.as_mut().unwrap();
+
+                if let Some(retry_at) = current.retry_at {
+                    let now = Instant::now();
+
+                    if now < retry_at {
+                        timer::wake_at(&mut current.wakeup, retry_at);
+                        return Ok(Async::NotReady);
+                    }
+
+                    current.retry_at = None;
+                    current.wakeup = None;
+                }
+
+                current.attempt
+            };
+
+            let e = match f(&mut self.inner) {
+                Err(e) => e,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                res => {
+                    self.current = None;
+                    return res;
+                }
+            };
+
+            let transient = self.policy.is_transient(&e)
+                && !(op == FileOp::TryLock && e.kind() == ErrorKind::WouldBlock);
+
+            if !transient || attempt >= self.policy.max_attempts {
+                self.current = None;
+
+                if attempt == 1 {
+                    return Err(e);
+                }
+
+                return Err(RetriesExhausted {
+                    attempts: attempt,
+                    error: e,
+                }
+                .into());
+            }
+
+            let current = self.current.as_mut().unwrap();
+            current.attempt += 1;
+            current.retry_at = Some(Instant::now() + self.policy.backoff(attempt));
+        }
+    }
+}
+
+impl<T> AsyncFile for RetryingFile<T>
+where
+    T: AsyncFile,
+{
+    type Cloned = RetryingFile<T::Cloned>;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        self.poll_op(FileOp::Seek, |file| file.poll_seek(pos))
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::SyncAll, |file| file.poll_sync_all())
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::SyncData, |file| file.poll_sync_data())
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        self.poll_op(FileOp::SetLen, |file| file.poll_set_len(size))
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        self.poll_op(FileOp::Metadata, |file| file.poll_metadata())
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<Self::Cloned, Error> {
+        let cloned = try_ready!(self.poll_op(FileOp::TryClone, |file| file.poll_try_clone()));
+
+        Ok(Async::Ready(RetryingFile::new(cloned, self.policy.clone())))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        self.poll_op(FileOp::SetPermissions, |file| {
+            file.poll_set_permissions(perm.clone())
+        })
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        self.poll_op(FileOp::Read, |file| file.poll_read(buf))
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        self.poll_op(FileOp::Write, |file| file.poll_write(buf))
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        self.poll_op(FileOp::ReadAt, |file| file.poll_read_at(buf, offset))
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        self.poll_op(FileOp::WriteAt, |file| file.poll_write_at(buf, offset))
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_op(FileOp::Lock, |file| file.poll_lock(mode))
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_op(FileOp::TryLock, |file| file.poll_try_lock(mode))
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::Unlock, |file| file.poll_unlock())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.inner.path()
+    }
+
+    fn start_operation(&mut self) {
+        self.current = None;
+        self.inner.start_operation()
+    }
+}
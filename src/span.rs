@@ -0,0 +1,153 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! `tracing` spans for the operation futures, behind the `tracing` feature. Without it these
+//! are empty and cost nothing.
+
+use std::{fmt, path::Path};
+
+use futures::Poll;
+#[cfg(feature = "tracing")]
+use futures::Async;
+#[cfg(feature = "tracing")]
+use std::sync::OnceLock;
+#[cfg(feature = "tracing")]
+use tracing::{field, Level, Span};
+
+/// The span an operation future runs in, opened the first time it's polled.
+pub(crate) struct OpSpan {
+    #[cfg(feature = "tracing")]
+    op: &'static str,
+    #[cfg(feature = "tracing")]
+    args: Option<String>,
+    #[cfg(feature = "tracing")]
+    path: Option<String>,
+    #[cfg(feature = "tracing")]
+    span: OnceLock<Span>,
+}
+
+impl OpSpan {
+    /// A span for `op`, e.g. `"set_len"`, called with `args`, e.g. `"30"`.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn new(op: &'static str, args: fmt::Arguments) -> Self {
+        let args = if tracing::level_enabled!(Level::DEBUG) {
+            Some(args.to_string())
+        } else {
+            None
+        };
+
+        OpSpan {
+            op,
+            args,
+            path: None,
+            span: OnceLock::new(),
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn new(_op: &'static str, _args: fmt::Arguments) -> Self {
+        OpSpan {}
+    }
+
+    /// Name `path` in the span, for futures that work on a path before they have a file.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn with_path(mut self, path: &Path) -> Self {
+        if tracing::level_enabled!(Level::DEBUG) {
+            self.path = Some(path.display().to_string());
+        }
+
+        self
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn with_path(self, _path: &Path) -> Self {
+        self
+    }
+
+    /// The span to poll in, opening it for the file at `path` if this is the first poll.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn open(&self, path: Option<&Path>) -> ActiveSpan {
+        let span = self.span.get_or_init(|| {
+            let span = tracing::debug_span!(
+                "file_op",
+                op = self.op,
+                args = self.args.as_ref().map_or("", String::as_str),
+                path = field::Empty,
+                outcome = field::Empty,
+            );
+
+            if let Some(path) = path {
+                span.record("path", field::display(path.display()));
+            } else if let Some(ref path) = self.path {
+                span.record("path", path.as_str());
+            }
+
+            span
+        });
+
+        ActiveSpan { span: span.clone() }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn open(&self, _path: Option<&Path>) -> ActiveSpan {
+        ActiveSpan {}
+    }
+}
+
+/// An opened `OpSpan`, for the duration of one poll.
+pub(crate) struct ActiveSpan {
+    #[cfg(feature = "tracing")]
+    span: Span,
+}
+
+impl ActiveSpan {
+    /// Run `poll` inside the span, reporting what it returned.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn poll<T, E, F>(self, poll: F) -> Poll<T, E>
+    where
+        E: fmt::Display,
+        F: FnOnce() -> Poll<T, E>,
+    {
+        let _entered = self.span.enter();
+        let res = poll();
+
+        match res {
+            Ok(Async::NotReady) => tracing::trace!("not ready"),
+            Ok(Async::Ready(_)) => {
+                self.span.record("outcome", "ready");
+                tracing::debug!("ready");
+            }
+            Err(ref e) => {
+                self.span.record("outcome", "error");
+                tracing::debug!(error = %e, "failed");
+            }
+        }
+
+        res
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn poll<T, E, F>(self, poll: F) -> Poll<T, E>
+    where
+        E: fmt::Display,
+        F: FnOnce() -> Poll<T, E>,
+    {
+        poll()
+    }
+}
@@ -0,0 +1,514 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! `std::future` support, enabled by the `futures03` feature.
+//!
+//! Every future in this crate also implements `std::future::Future`, so it can be `.await`ed
+//! directly. The operation futures, like `Seek` and `SyncAll`, poll their file through this
+//! module's `AsyncFile`, whose `poll_*` methods take a `Context`. They resolve to the same
+//! values as their futures 0.1 impls: the file comes back on success and inside a `FileError`
+//! on failure.
+//!
+//! Every futures 0.1 `AsyncFile` is also an `AsyncFile` here, so `tokio_fs::File` and the crate's
+//! wrappers work as they are. Bear in mind that `tokio_fs::File` does its work on the blocking
+//! pool of a tokio 0.1 runtime, so its futures still need to run on one, for example through
+//! `futures::compat`. The same goes for the path-based futures like `read` and `atomic_write`,
+//! and for `SyncPolicy::DataThenDirectory`, which opens the directory with `tokio_fs`.
+
+use std::{
+    fs::Permissions,
+    io::{Error, Result, SeekFrom},
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{self as futures01, Async};
+use futures03::{compat::Compat01As03, task::noop_waker, Future};
+
+use driver::Driver;
+use {
+    AtomicWrite, Commit, CreateAtomicFile, FileMetadata, GetMetadata, Lock, LockMode, Read,
+    ReadAt, ReadExact, ReadFile, ReadToEnd, ReadToString, Seek, SetLen, SetPermissions, SyncAll,
+    SyncData, SyncPolicy, SyncWith, TryClone, Unlock, Write, WriteAll, WriteAt, WriteFile,
+};
+
+/// The `Context`-taking counterpart of the crate's `AsyncFile`.
+///
+/// The provided methods return the same futures as the futures 0.1 trait; with this trait
+/// implemented they're `std::future::Future`s too.
+pub trait AsyncFile: Sized {
+    /// The new handle `try_clone` resolves to.
+    type Cloned: AsyncFile;
+
+    fn poll_seek(&mut self, cx: &mut Context, pos: SeekFrom) -> Poll<Result<u64>>;
+    fn poll_sync_all(&mut self, cx: &mut Context) -> Poll<Result<()>>;
+    fn poll_sync_data(&mut self, cx: &mut Context) -> Poll<Result<()>>;
+    fn poll_set_len(&mut self, cx: &mut Context, size: u64) -> Poll<Result<()>>;
+    fn poll_metadata(&mut self, cx: &mut Context) -> Poll<Result<FileMetadata>>;
+    fn poll_try_clone(&mut self, cx: &mut Context) -> Poll<Result<Self::Cloned>>;
+    fn poll_set_permissions(&mut self, cx: &mut Context, perm: Permissions) -> Poll<Result<()>>;
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<Result<usize>>;
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<Result<usize>>;
+    fn poll_read_at(
+        &mut self,
+        cx: &mut Context,
+        buf: &mut [u8],
+        offset: u64,
+    ) -> Poll<Result<usize>>;
+    fn poll_write_at(&mut self, cx: &mut Context, buf: &[u8], offset: u64) -> Poll<Result<usize>>;
+    fn poll_lock(&mut self, cx: &mut Context, mode: LockMode) -> Poll<Result<()>>;
+    fn poll_try_lock(&mut self, cx: &mut Context, mode: LockMode) -> Poll<Result<()>>;
+    fn poll_unlock(&mut self, cx: &mut Context) -> Poll<Result<()>>;
+
+    /// The path the file was opened at, if it's known. Only used to describe the file, e.g. in
+    /// `tracing` spans.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Called by the methods below as they create an operation future. See the futures 0.1
+    /// trait's `start_operation`.
+    fn start_operation(&mut self) {}
+
+    fn seek(mut self, pos: SeekFrom) -> Seek<Self> {
+        self.start_operation();
+        Seek::new(self, pos)
+    }
+
+    fn sync_all(mut self) -> SyncAll<Self> {
+        self.start_operation();
+        SyncAll::new(self)
+    }
+
+    fn sync_data(mut self) -> SyncData<Self> {
+        self.start_operation();
+        SyncData::new(self)
+    }
+
+    fn set_len(mut self, size: u64) -> SetLen<Self> {
+        self.start_operation();
+        SetLen::new(self, size)
+    }
+
+    fn metadata(mut self) -> GetMetadata<Self> {
+        self.start_operation();
+        GetMetadata::new(self)
+    }
+
+    fn try_clone(mut self) -> TryClone<Self> {
+        self.start_operation();
+        TryClone::new(self)
+    }
+
+    fn set_permissions(mut self, perm: Permissions) -> SetPermissions<Self> {
+        self.start_operation();
+        SetPermissions::new(self, perm)
+    }
+
+    /// Read some bytes into `buf`, resolving to the file, the buffer and the number of bytes read.
+    fn read<B>(mut self, buf: B) -> Read<Self, B>
+    where
+        B: AsMut<[u8]>,
+    {
+        self.start_operation();
+        Read::new(self, buf)
+    }
+
+    /// Write some bytes from `buf`, resolving to the file, the buffer and the number of bytes
+    /// written.
+    fn write<B>(mut self, buf: B) -> Write<Self, B>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.start_operation();
+        Write::new(self, buf)
+    }
+
+    /// Fill all of `buf`, failing with `UnexpectedEof` if the file ends first.
+    fn read_exact<B>(mut self, buf: B) -> ReadExact<Self, B>
+    where
+        B: AsMut<[u8]>,
+    {
+        self.start_operation();
+        ReadExact::new(self, buf)
+    }
+
+    /// Write all of `buf`, failing with `WriteZero` if the file stops accepting bytes.
+    fn write_all<B>(mut self, buf: B) -> WriteAll<Self, B>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.start_operation();
+        WriteAll::new(self, buf)
+    }
+
+    /// Read some bytes into `buf` from `offset`, without using or moving the file's cursor.
+    fn read_at<B>(mut self, offset: u64, buf: B) -> ReadAt<Self, B>
+    where
+        B: AsMut<[u8]>,
+    {
+        self.start_operation();
+        ReadAt::new(self, offset, buf)
+    }
+
+    /// Write some bytes from `buf` at `offset`, without using or moving the file's cursor.
+    fn write_at<B>(mut self, offset: u64, buf: B) -> WriteAt<Self, B>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.start_operation();
+        WriteAt::new(self, offset, buf)
+    }
+
+    /// Read the rest of the file from the cursor on, failing with `LimitExceeded` if more than
+    /// `limit` bytes are left.
+    fn read_to_end(mut self, limit: u64) -> ReadToEnd<Self> {
+        self.start_operation();
+        ReadToEnd::new(self, limit)
+    }
+
+    /// Read the rest of the file from the cursor on as UTF-8, failing with `LimitExceeded` if
+    /// more than `limit` bytes are left.
+    fn read_to_string(mut self, limit: u64) -> ReadToString<Self> {
+        self.start_operation();
+        ReadToString::new(self, limit)
+    }
+
+    /// Wait for a shared advisory lock on the file.
+    fn lock_shared(mut self) -> Lock<Self> {
+        self.start_operation();
+        Lock::new(self, LockMode::Shared, true)
+    }
+
+    /// Wait for an exclusive advisory lock on the file.
+    fn lock_exclusive(mut self) -> Lock<Self> {
+        self.start_operation();
+        Lock::new(self, LockMode::Exclusive, true)
+    }
+
+    /// Take a shared advisory lock on the file, failing with `WouldBlock` if that would mean
+    /// waiting.
+    fn try_lock_shared(mut self) -> Lock<Self> {
+        self.start_operation();
+        Lock::new(self, LockMode::Shared, false)
+    }
+
+    /// Take an exclusive advisory lock on the file, failing with `WouldBlock` if that would mean
+    /// waiting.
+    fn try_lock_exclusive(mut self) -> Lock<Self> {
+        self.start_operation();
+        Lock::new(self, LockMode::Exclusive, false)
+    }
+
+    /// Release any advisory lock held on the file.
+    fn unlock(mut self) -> Unlock<Self> {
+        self.start_operation();
+        Unlock::new(self)
+    }
+
+    /// Flush the file to disk as thoroughly as `policy` asks for.
+    fn sync_with(mut self, policy: SyncPolicy) -> SyncWith<Self> {
+        self.start_operation();
+        SyncWith::new(self, policy)
+    }
+}
+
+/// Runs the futures 0.1 `poll_*` method in a task that wakes `cx` when the file is ready.
+impl<T> AsyncFile for T
+where
+    T: ::AsyncFile,
+{
+    type Cloned = T::Cloned;
+
+    fn poll_seek(&mut self, cx: &mut Context, pos: SeekFrom) -> Poll<Result<u64>> {
+        poll_01(cx, || ::AsyncFile::poll_seek(self, pos))
+    }
+
+    fn poll_sync_all(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        poll_01(cx, || ::AsyncFile::poll_sync_all(self))
+    }
+
+    fn poll_sync_data(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        poll_01(cx, || ::AsyncFile::poll_sync_data(self))
+    }
+
+    fn poll_set_len(&mut self, cx: &mut Context, size: u64) -> Poll<Result<()>> {
+        poll_01(cx, || ::AsyncFile::poll_set_len(self, size))
+    }
+
+    fn poll_metadata(&mut self, cx: &mut Context) -> Poll<Result<FileMetadata>> {
+        poll_01(cx, || ::AsyncFile::poll_metadata(self))
+    }
+
+    fn poll_try_clone(&mut self, cx: &mut Context) -> Poll<Result<Self::Cloned>> {
+        poll_01(cx, || ::AsyncFile::poll_try_clone(self))
+    }
+
+    fn poll_set_permissions(&mut self, cx: &mut Context, perm: Permissions) -> Poll<Result<()>> {
+        poll_01(cx, || ::AsyncFile::poll_set_permissions(self, perm.clone()))
+    }
+
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<Result<usize>> {
+        poll_01(cx, || ::AsyncFile::poll_read(self, buf))
+    }
+
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<Result<usize>> {
+        poll_01(cx, || ::AsyncFile::poll_write(self, buf))
+    }
+
+    fn poll_read_at(
+        &mut self,
+        cx: &mut Context,
+        buf: &mut [u8],
+        offset: u64,
+    ) -> Poll<Result<usize>> {
+        poll_01(cx, || ::AsyncFile::poll_read_at(self, buf, offset))
+    }
+
+    fn poll_write_at(&mut self, cx: &mut Context, buf: &[u8], offset: u64) -> Poll<Result<usize>> {
+        poll_01(cx, || ::AsyncFile::poll_write_at(self, buf, offset))
+    }
+
+    fn poll_lock(&mut self, cx: &mut Context, mode: LockMode) -> Poll<Result<()>> {
+        poll_01(cx, || ::AsyncFile::poll_lock(self, mode))
+    }
+
+    fn poll_try_lock(&mut self, cx: &mut Context, mode: LockMode) -> Poll<Result<()>> {
+        poll_01(cx, || ::AsyncFile::poll_try_lock(self, mode))
+    }
+
+    fn poll_unlock(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        poll_01(cx, || ::AsyncFile::poll_unlock(self))
+    }
+
+    fn path(&self) -> Option<&Path> {
+        ::AsyncFile::path(self)
+    }
+
+    fn start_operation(&mut self) {
+        ::AsyncFile::start_operation(self)
+    }
+}
+
+/// Poll a futures 0.1 style `poll` function from inside a `std::future` task.
+fn poll_01<T, E, F>(cx: &mut Context, f: F) -> Poll<::std::result::Result<T, E>>
+where
+    F: FnMut() -> futures01::Poll<T, E>,
+{
+    Pin::new(&mut Compat01As03::new(futures01::future::poll_fn(f))).poll(cx)
+}
+
+fn into_01<T, E>(poll: Poll<::std::result::Result<T, E>>) -> futures01::Poll<T, E> {
+    match poll {
+        Poll::Ready(Ok(item)) => Ok(Async::Ready(item)),
+        Poll::Ready(Err(e)) => Err(e),
+        Poll::Pending => Ok(Async::NotReady),
+    }
+}
+
+/// Drive an operation future's `poll_with` from a `std::future::Future` impl.
+pub(crate) fn poll_with_context<T, E, F>(
+    cx: &mut Context,
+    f: F,
+) -> Poll<::std::result::Result<T, E>>
+where
+    F: FnOnce(&mut WithContext) -> futures01::Poll<T, E>,
+{
+    match f(&mut WithContext { cx }) {
+        Ok(Async::Ready(item)) => Poll::Ready(Ok(item)),
+        Ok(Async::NotReady) => Poll::Pending,
+        Err(e) => Poll::Ready(Err(e)),
+    }
+}
+
+/// Polls files through this module's `AsyncFile` with the `Context` of the current task.
+pub(crate) struct WithContext<'a, 'b: 'a> {
+    cx: &'a mut Context<'b>,
+}
+
+impl<'a, 'b, T> Driver<T> for WithContext<'a, 'b>
+where
+    T: AsyncFile,
+{
+    type Cloned = T::Cloned;
+
+    fn poll_seek(&mut self, file: &mut T, pos: SeekFrom) -> futures01::Poll<u64, Error> {
+        into_01(file.poll_seek(self.cx, pos))
+    }
+
+    fn poll_sync_all(&mut self, file: &mut T) -> futures01::Poll<(), Error> {
+        into_01(file.poll_sync_all(self.cx))
+    }
+
+    fn poll_sync_data(&mut self, file: &mut T) -> futures01::Poll<(), Error> {
+        into_01(file.poll_sync_data(self.cx))
+    }
+
+    fn poll_set_len(&mut self, file: &mut T, size: u64) -> futures01::Poll<(), Error> {
+        into_01(file.poll_set_len(self.cx, size))
+    }
+
+    fn poll_metadata(&mut self, file: &mut T) -> futures01::Poll<FileMetadata, Error> {
+        into_01(file.poll_metadata(self.cx))
+    }
+
+    fn poll_try_clone(&mut self, file: &mut T) -> futures01::Poll<T::Cloned, Error> {
+        into_01(file.poll_try_clone(self.cx))
+    }
+
+    fn poll_set_permissions(
+        &mut self,
+        file: &mut T,
+        perm: Permissions,
+    ) -> futures01::Poll<(), Error> {
+        into_01(file.poll_set_permissions(self.cx, perm))
+    }
+
+    fn poll_read(&mut self, file: &mut T, buf: &mut [u8]) -> futures01::Poll<usize, Error> {
+        into_01(file.poll_read(self.cx, buf))
+    }
+
+    fn poll_write(&mut self, file: &mut T, buf: &[u8]) -> futures01::Poll<usize, Error> {
+        into_01(file.poll_write(self.cx, buf))
+    }
+
+    fn poll_read_at(
+        &mut self,
+        file: &mut T,
+        buf: &mut [u8],
+        offset: u64,
+    ) -> futures01::Poll<usize, Error> {
+        into_01(file.poll_read_at(self.cx, buf, offset))
+    }
+
+    fn poll_write_at(
+        &mut self,
+        file: &mut T,
+        buf: &[u8],
+        offset: u64,
+    ) -> futures01::Poll<usize, Error> {
+        into_01(file.poll_write_at(self.cx, buf, offset))
+    }
+
+    fn poll_lock(&mut self, file: &mut T, mode: LockMode) -> futures01::Poll<(), Error> {
+        into_01(file.poll_lock(self.cx, mode))
+    }
+
+    fn poll_try_lock(&mut self, file: &mut T, mode: LockMode) -> futures01::Poll<(), Error> {
+        into_01(file.poll_try_lock(self.cx, mode))
+    }
+
+    fn poll_unlock(&mut self, file: &mut T) -> futures01::Poll<(), Error> {
+        into_01(file.poll_unlock(self.cx))
+    }
+
+    fn poll_future<F>(&mut self, future: &mut F) -> futures01::Poll<F::Item, F::Error>
+    where
+        F: futures01::Future,
+    {
+        into_01(poll_compat(future, self.cx))
+    }
+
+    fn release(&self) -> fn(&mut T) {
+        // There's no task to wake from a destructor, so the lock only gets released if the file
+        // can do it straight away.
+        fn release<T: AsyncFile>(file: &mut T) {
+            let waker = noop_waker();
+            let _ = file.poll_unlock(&mut Context::from_waker(&waker));
+        }
+
+        release::<T>
+    }
+}
+
+/// Poll a futures 0.1 future that has nothing but `Unpin` state as a `std::future::Future`.
+fn poll_compat<F>(
+    future: &mut F,
+    cx: &mut Context,
+) -> Poll<::std::result::Result<F::Item, F::Error>>
+where
+    F: futures01::Future,
+{
+    Pin::new(&mut Compat01As03::new(future)).poll(cx)
+}
+
+impl<T> Future for TryClone<T>
+where
+    T: AsyncFile + Unpin,
+{
+    type Output = ::std::result::Result<(T, T::Cloned), ::FileError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let span = this.span.open(this.file().and_then(AsyncFile::path));
+
+        poll_with_context(cx, |driver| span.poll(|| this.poll_with(driver)))
+    }
+}
+
+impl<P> Future for ReadFile<P>
+where
+    P: AsRef<Path> + Send + Unpin + 'static,
+{
+    type Output = Result<Vec<u8>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        poll_compat(&mut *self, cx)
+    }
+}
+
+impl<P, C> Future for WriteFile<P, C>
+where
+    P: AsRef<Path> + Send + Unpin + 'static,
+    C: AsRef<[u8]> + Unpin,
+{
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        poll_compat(&mut *self, cx)
+    }
+}
+
+impl Future for CreateAtomicFile {
+    type Output = Result<::AtomicFile>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        poll_compat(&mut *self, cx)
+    }
+}
+
+impl Future for Commit {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        poll_compat(&mut *self, cx)
+    }
+}
+
+impl<C> Future for AtomicWrite<C>
+where
+    C: AsRef<[u8]> + Unpin,
+{
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        poll_compat(&mut *self, cx)
+    }
+}
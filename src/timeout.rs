@@ -0,0 +1,202 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs::Permissions,
+    io::{Error, ErrorKind, SeekFrom},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use futures::{Async, Poll};
+
+use timer::{self, Wakeup};
+use {AsyncFile, FileMetadata, FileOp, LockMode};
+
+/// The operation in flight, and when it runs out of time.
+#[derive(Debug)]
+struct Current {
+    op: FileOp,
+    deadline: Instant,
+    /// Wakes the task at the deadline once the operation has reported `NotReady`.
+    wakeup: Option<Wakeup>,
+}
+
+/// An `AsyncFile` whose operations fail with `TimedOut` if they take longer than a timeout.
+///
+/// Each call to one of the file's `poll_*` methods gets the whole timeout, counted from its first
+/// poll, so a `WriteAll` gets it for every write it makes. If the file reports `NotReady`, a timer
+/// thread shared by the whole process wakes the task at the deadline, so this needs no runtime
+/// of its own. An operation that times out hands back the file in its `FileError`, as any other
+/// failure does.
+///
+/// Timing out doesn't stop the operation underneath. `PooledFile` carries on with it in the
+/// background. The file's next operation waits for it to finish and discards its result, then
+/// runs on its own, even if it's an identical retry. `tokio_fs::File` does its work inside
+/// `poll`, so an operation that blocks there can't be timed out.
+#[derive(Debug)]
+pub struct TimedFile<T> {
+    inner: T,
+    timeout: Duration,
+    current: Option<Current>,
+}
+
+impl<T> TimedFile<T>
+where
+    T: AsyncFile,
+{
+    pub fn new(inner: T, timeout: Duration) -> Self {
+        TimedFile {
+            inner,
+            timeout,
+            current: None,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn poll_op<R, F>(&mut self, op: FileOp, f: F) -> Poll<R, Error>
+    where
+        F: FnOnce(&mut T) -> Poll<R, Error>,
+    {
+        let restart = match self.current {
+            Some(ref current) => current.op != op,
+            None => true,
+        };
+
+        if restart {
+            self.current = Some(Current {
+                op,
+                deadline: Instant::now() + self.timeout,
+                wakeup: None,
+            });
+        }
+
+        if Instant::now() >= self.current.as_ref().unwrap().deadline {
+            self.current = None;
+            return Err(Error::new(
+                ErrorKind::TimedOut,
+                format!("timed out after {:?}", self.timeout),
+            ));
+        }
+
+        let res = f(&mut self.inner);
+
+        if let Ok(Async::NotReady) = res {
+            let current = self.current.as_mut().unwrap();
+            timer::wake_at(&mut current.wakeup, current.deadline);
+
+            return res;
+        }
+
+        // Dropping the wake-up cancels it.
+        self.current = None;
+        res
+    }
+}
+
+impl<T> AsyncFile for TimedFile<T>
+where
+    T: AsyncFile,
+{
+    type Cloned = TimedFile<T::Cloned>;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        self.poll_op(FileOp::Seek, |file| file.poll_seek(pos))
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::SyncAll, |file| file.poll_sync_all())
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::SyncData, |file| file.poll_sync_data())
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        self.poll_op(FileOp::SetLen, |file| file.poll_set_len(size))
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        self.poll_op(FileOp::Metadata, |file| file.poll_metadata())
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<Self::Cloned, Error> {
+        let cloned = try_ready!(self.poll_op(FileOp::TryClone, |file| file.poll_try_clone()));
+
+        Ok(Async::Ready(TimedFile::new(cloned, self.timeout)))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        self.poll_op(FileOp::SetPermissions, |file| {
+            file.poll_set_permissions(perm)
+        })
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        self.poll_op(FileOp::Read, |file| file.poll_read(buf))
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        self.poll_op(FileOp::Write, |file| file.poll_write(buf))
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        self.poll_op(FileOp::ReadAt, |file| file.poll_read_at(buf, offset))
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        self.poll_op(FileOp::WriteAt, |file| file.poll_write_at(buf, offset))
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_op(FileOp::Lock, |file| file.poll_lock(mode))
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.poll_op(FileOp::TryLock, |file| file.poll_try_lock(mode))
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        self.poll_op(FileOp::Unlock, |file| file.poll_unlock())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.inner.path()
+    }
+
+    fn start_operation(&mut self) {
+        self.current = None;
+        self.inner.start_operation()
+    }
+}
@@ -0,0 +1,148 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A timer thread, shared by everything in the crate that needs to wake a task at a deadline.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError},
+    thread,
+    time::Instant,
+};
+
+use futures::task::{self, Task};
+
+/// Wake-ups that haven't happened yet, ordered by deadline.
+#[derive(Default)]
+struct Wakeups {
+    /// Tells apart wake-ups due at the same instant.
+    next_id: u64,
+    tasks: BTreeMap<(Instant, u64), Task>,
+}
+
+#[derive(Default)]
+struct Timer {
+    wakeups: Mutex<Wakeups>,
+    changed: Condvar,
+}
+
+impl Timer {
+    fn lock(&self) -> MutexGuard<'_, Wakeups> {
+        self.wakeups.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn timer() -> &'static Timer {
+    static TIMER: OnceLock<Timer> = OnceLock::new();
+
+    let mut started = false;
+    let timer = TIMER.get_or_init(|| {
+        started = true;
+        Timer::default()
+    });
+
+    if started {
+        thread::Builder::new()
+            .name("file-futures-timer".to_owned())
+            .spawn(move || run(timer))
+            .expect("Failed to start timer thread");
+    }
+
+    timer
+}
+
+fn run(timer: &Timer) {
+    let mut wakeups = timer.lock();
+
+    loop {
+        let now = Instant::now();
+        let mut due = Vec::new();
+
+        while let Some(entry) = wakeups.tasks.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+
+            due.push(entry.remove());
+        }
+
+        if !due.is_empty() {
+            drop(wakeups);
+            due.into_iter().for_each(|task| task.notify());
+            wakeups = timer.lock();
+            continue;
+        }
+
+        wakeups = match wakeups.tasks.keys().next() {
+            Some(&(deadline, _)) => {
+                let res = timer.changed.wait_timeout(wakeups, deadline - now);
+
+                res.unwrap_or_else(PoisonError::into_inner).0
+            }
+            None => timer
+                .changed
+                .wait(wakeups)
+                .unwrap_or_else(PoisonError::into_inner),
+        };
+    }
+}
+
+/// A pending wake-up of a task. Dropping it cancels the wake-up.
+#[derive(Debug)]
+pub(crate) struct Wakeup {
+    key: (Instant, u64),
+}
+
+impl Wakeup {
+    /// Wake the current task at `deadline`.
+    fn new(deadline: Instant) -> Self {
+        let timer = timer();
+        let mut wakeups = timer.lock();
+
+        let key = (deadline, wakeups.next_id);
+        wakeups.next_id += 1;
+        wakeups.tasks.insert(key, task::current());
+        timer.changed.notify_one();
+
+        Wakeup { key }
+    }
+
+    /// Wake the current task instead of the one the wake-up was made for, or straight away if
+    /// the deadline has already passed.
+    fn rearm(&self) {
+        match timer().lock().tasks.get_mut(&self.key) {
+            Some(task) => *task = task::current(),
+            None => task::current().notify(),
+        }
+    }
+}
+
+impl Drop for Wakeup {
+    fn drop(&mut self) {
+        timer().lock().tasks.remove(&self.key);
+    }
+}
+
+/// Wake the current task at `deadline`, reusing `wakeup` if it's already set for then.
+pub(crate) fn wake_at(wakeup: &mut Option<Wakeup>, deadline: Instant) {
+    match *wakeup {
+        Some(ref wakeup) if wakeup.key.0 == deadline => wakeup.rearm(),
+        _ => *wakeup = Some(Wakeup::new(deadline)),
+    }
+}
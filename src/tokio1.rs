@@ -0,0 +1,183 @@
+/*
+ * This file is part of Tokio File Futures.
+ *
+ * Copyright © 2017 Riley Trautman
+ *
+ * Tokio File Futures is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Tokio File Futures is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Tokio File Futures.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A tokio 1 backend, enabled by the `tokio1` feature.
+//!
+//! `AsyncFile` isn't implemented for `tokio::fs::File` itself; see `File` for why. Instead, a
+//! `tokio::fs::File` converts into a `File` with `File::from_tokio`, which waits for whatever
+//! the tokio file has in flight, or with `File::try_from` when nothing is.
+
+use std::{
+    any::Any,
+    convert::TryFrom,
+    fs::{File as StdFile, Permissions},
+    io::{Error, SeekFrom},
+    pin::Pin,
+};
+
+use futures::{Async, Poll};
+use futures03::{compat::Compat, future::poll_fn, Future, FutureExt};
+use tokio::{runtime::Handle, task::JoinHandle};
+
+use offload::{Done, OffloadedFile, Spawner};
+use {AsyncFile, FileMetadata, LockMode};
+
+/// A file whose operations run on the blocking pool of a tokio 1 runtime.
+///
+/// `tokio::fs::File` can't implement `AsyncFile` itself, since it has nowhere to keep an
+/// operation that's in flight between polls. This wraps a `std::fs::File` and keeps that
+/// operation, so generic code written against `AsyncFile` or `std_future::AsyncFile` works the
+/// same here as on `tokio_fs::File`.
+///
+/// Operations are spawned on the runtime the file was created with, so a `File` can be polled
+/// from any executor. As with `PooledFile`, one whose future is dropped still finishes in the
+/// background.
+#[derive(Debug)]
+pub struct File {
+    inner: OffloadedFile<Handle>,
+}
+
+impl File {
+    /// Wrap `file`, running its operations on the current tokio 1 runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio 1 runtime.
+    pub fn from_std(file: StdFile) -> Self {
+        File::with_handle(file, Handle::current())
+    }
+
+    /// Wrap `file`, running its operations on the runtime `runtime` belongs to.
+    pub fn with_handle(file: StdFile, runtime: Handle) -> Self {
+        File {
+            inner: OffloadedFile::new(file, runtime),
+        }
+    }
+
+    /// Convert a `tokio::fs::File`, once any operation it has in flight finishes, running the
+    /// new file's operations on the current tokio 1 runtime.
+    ///
+    /// # Panics
+    ///
+    /// The future panics if polled outside of a tokio 1 runtime.
+    pub fn from_tokio(file: tokio::fs::File) -> impl Future<Output = File> {
+        file.into_std().map(File::from_std)
+    }
+}
+
+/// Converts a `tokio::fs::File` with no operation in flight, handing it back if it has one.
+///
+/// # Panics
+///
+/// Panics if called outside of a tokio 1 runtime.
+impl TryFrom<tokio::fs::File> for File {
+    type Error = tokio::fs::File;
+
+    fn try_from(file: tokio::fs::File) -> Result<Self, Self::Error> {
+        file.try_into_std().map(File::from_std)
+    }
+}
+
+impl Spawner for Handle {
+    type Task = JoinHandle<Done>;
+
+    fn spawn<F>(&self, f: F) -> Self::Task
+    where
+        F: FnOnce() -> Done + Send + 'static,
+    {
+        self.spawn_blocking(f)
+    }
+
+    fn poll_task(task: &mut Self::Task) -> Poll<Box<dyn Any + Send>, Error> {
+        // Run the poll in a `std::future` context that notifies the current futures 0.1 task.
+        let mut joined = Compat::new(poll_fn(|cx| {
+            Pin::new(&mut *task)
+                .poll(cx)
+                .map(|joined| joined.map_err(Error::other).and_then(|done| done))
+        }));
+
+        futures::Future::poll(&mut joined)
+    }
+}
+
+impl AsyncFile for File {
+    type Cloned = File;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        self.inner.poll_seek(pos)
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        self.inner.poll_sync_all()
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        self.inner.poll_sync_data()
+    }
+
+    fn poll_set_len(&mut self, size: u64) -> Poll<(), Error> {
+        self.inner.poll_set_len(size)
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        self.inner.poll_metadata()
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<File, Error> {
+        let file = try_ready!(self.inner.poll_try_clone());
+
+        Ok(Async::Ready(File::with_handle(file, self.inner.spawner().clone())))
+    }
+
+    fn poll_set_permissions(&mut self, perm: Permissions) -> Poll<(), Error> {
+        self.inner.poll_set_permissions(perm)
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        self.inner.poll_read(buf)
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        self.inner.poll_write(buf)
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        self.inner.poll_read_at(buf, offset)
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        self.inner.poll_write_at(buf, offset)
+    }
+
+    fn poll_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.inner.poll_lock(mode)
+    }
+
+    fn poll_try_lock(&mut self, mode: LockMode) -> Poll<(), Error> {
+        self.inner.poll_try_lock(mode)
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        self.inner.poll_unlock()
+    }
+
+    fn start_operation(&mut self) {
+        self.inner.start_operation()
+    }
+}
@@ -0,0 +1,93 @@
+extern crate file_futures;
+extern crate futures;
+extern crate tokio;
+
+use std::{env, fs, io, path::PathBuf};
+
+use file_futures::{atomic_write, AsyncFile, AtomicFile, SyncPolicy};
+use futures::Future;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn entries(dir: &PathBuf) -> Vec<String> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+
+    entries.sort();
+    entries
+}
+
+fn block_on<F>(future: F) -> Result<F::Item, F::Error>
+where
+    F: Future + Send + 'static,
+    F::Item: Send + 'static,
+    F::Error: Send + 'static,
+{
+    tokio::runtime::Runtime::new().unwrap().block_on(future)
+}
+
+#[test]
+fn atomic_write_replaces_the_file() {
+    let dir = scratch_dir("file-futures-atomic-write");
+    let path = dir.join("config");
+    fs::write(&path, "old").unwrap();
+
+    block_on(atomic_write(path.clone(), "new", SyncPolicy::All)).unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), b"new");
+    assert_eq!(entries(&dir), vec!["config"]);
+}
+
+#[test]
+fn atomic_write_fails_cleanly() {
+    let dir = scratch_dir("file-futures-atomic-write-missing");
+    let path = dir.join("missing").join("config");
+
+    let err = block_on(atomic_write(path, "new", SyncPolicy::Data)).unwrap_err();
+
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert!(entries(&dir).is_empty());
+}
+
+#[test]
+fn commit_replaces_the_file() {
+    let dir = scratch_dir("file-futures-atomic-commit");
+    let path = dir.join("data");
+    fs::write(&path, "old").unwrap();
+
+    let future = AtomicFile::create(path.clone(), SyncPolicy::Data)
+        .and_then(|file| file.write_all("hello ").map_err(Into::into))
+        .and_then(|(file, _, _)| file.write_all("world").map_err(Into::into))
+        .and_then(|(file, _, _)| {
+            assert_eq!(fs::read(file.target()).unwrap(), b"old");
+            file.commit()
+        });
+
+    block_on(future).unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), b"hello world");
+    assert_eq!(entries(&dir), vec!["data"]);
+}
+
+#[test]
+fn dropping_leaves_the_target_alone() {
+    let dir = scratch_dir("file-futures-atomic-drop");
+    let path = dir.join("data");
+    fs::write(&path, "old").unwrap();
+
+    let future = AtomicFile::create(path.clone(), SyncPolicy::None)
+        .and_then(|file| file.write_all("new").map_err(Into::into))
+        .map(|_| ());
+
+    block_on(future).unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), b"old");
+    assert_eq!(entries(&dir), vec!["data"]);
+}
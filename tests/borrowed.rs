@@ -0,0 +1,66 @@
+extern crate file_futures;
+extern crate futures;
+
+use std::io::{ErrorKind, SeekFrom};
+
+use file_futures::{borrowed, AsyncFile, MemFile};
+use futures::Future;
+
+struct Journal {
+    file: MemFile,
+    entries: usize,
+}
+
+impl Journal {
+    fn append(&mut self, entry: &'static str) -> borrowed::WriteAll<'_, MemFile, &'static str> {
+        self.entries += 1;
+        (&mut self.file).write_all(entry)
+    }
+}
+
+#[test]
+fn operations_on_a_borrow_leave_the_file_in_place() {
+    let mut journal = Journal {
+        file: MemFile::new(),
+        entries: 0,
+    };
+
+    journal.append("one\n").wait().unwrap();
+    journal.append("two\n").wait().unwrap();
+
+    assert_eq!(journal.entries, 2);
+    assert_eq!(journal.file.contents(), b"one\ntwo\n");
+}
+
+#[test]
+fn failures_hand_back_the_borrow() {
+    let mut file = MemFile::with_contents("abc");
+
+    let err = (&mut file).seek(SeekFrom::Current(-1)).wait().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    let (borrow, pos) = err.into_file().seek(SeekFrom::End(0)).wait().unwrap();
+    assert_eq!(pos, 3);
+    borrow.set_len(1).wait().unwrap();
+
+    assert_eq!(file.contents(), b"a");
+}
+
+#[test]
+fn locks_on_a_borrow_release_on_drop() {
+    let mut file = MemFile::new();
+    let other = file.reopen();
+
+    drop((&mut file).lock_exclusive().wait().unwrap());
+
+    other.try_lock_exclusive().wait().unwrap();
+}
+
+#[test]
+fn boxed_files_clone_to_the_inner_type() {
+    let (file, clone): (Box<MemFile>, MemFile) =
+        Box::new(MemFile::with_contents("abc")).try_clone().wait().unwrap();
+
+    clone.set_len(0).wait().unwrap();
+    assert_eq!(file.contents(), b"");
+}
@@ -0,0 +1,96 @@
+extern crate file_futures;
+extern crate futures;
+
+use std::{env, fs::OpenOptions, io::SeekFrom};
+
+use file_futures::{AsyncFile, Fault, FaultPlan, FaultyFile, FileOp, MemFile, Pool, PooledFile};
+use futures::{future, Async, Future};
+
+/// Poll `future` once inside a task, handing it back.
+fn poll_once<F: Future>(mut future: F) -> F {
+    future::lazy(move || {
+        let _ = future.poll();
+        Ok::<_, ()>(future)
+    })
+    .wait()
+    .unwrap()
+}
+
+#[test]
+fn unpolled_operations_leave_the_file_alone() {
+    let future = MemFile::with_contents("abc").set_len(0);
+    assert_eq!(future.get_ref().contents(), b"abc");
+
+    let file = future.into_inner();
+    assert_eq!(file.contents(), b"abc");
+}
+
+#[test]
+fn get_mut_reaches_the_file_before_the_operation() {
+    let mut future = MemFile::with_contents("abc").read_to_string(64);
+    future.get_mut().poll_seek(SeekFrom::Start(1)).unwrap();
+
+    let (_, contents) = future.wait().unwrap();
+    assert_eq!(contents, "bc");
+}
+
+#[test]
+fn abandoned_operations_keep_their_progress() {
+    let plan = FaultPlan::new(0)
+        .on_call(FileOp::Write, 0, Fault::ShortWrite(1))
+        .on_call(FileOp::Write, 1, Fault::NotReady);
+    let file = FaultyFile::new(MemFile::new(), plan);
+
+    let future = poll_once(file.write_all(b"abc"));
+    assert_eq!(future.get_ref().calls(FileOp::Write), 2);
+
+    let file = future.into_inner();
+    assert_eq!(file.get_ref().contents(), b"a");
+}
+
+#[test]
+fn abandoned_pooled_operations_still_finish() {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(env::temp_dir().join("file-futures-cancel-pool"))
+        .unwrap();
+    let file = PooledFile::with_pool(file, Pool::new(1));
+
+    let file = poll_once(file.set_len(30)).into_inner();
+
+    let (_, metadata) = file.metadata().wait().unwrap();
+    assert_eq!(metadata.len(), 30);
+}
+
+#[test]
+fn repeated_pooled_operations_run_again() {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(env::temp_dir().join("file-futures-cancel-repeat"))
+        .unwrap();
+    let file = PooledFile::with_pool(file, Pool::new(1));
+
+    // The pool may finish the seek before the first poll looks, which is fine as well.
+    let mut seek = file.seek(SeekFrom::Current(10));
+    let file = future::lazy(move || match seek.poll().unwrap() {
+        Async::Ready((file, _)) => Ok::<_, ()>(file),
+        Async::NotReady => Ok(seek.into_inner()),
+    })
+    .wait()
+    .unwrap();
+
+    let (_, pos) = file.seek(SeekFrom::Current(10)).wait().unwrap();
+    assert_eq!(pos, 20);
+}
+
+#[test]
+#[should_panic(expected = "Used future after completion")]
+fn into_inner_panics_once_resolved() {
+    poll_once(MemFile::new().sync_all()).into_inner();
+}
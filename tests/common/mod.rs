@@ -0,0 +1,181 @@
+#![allow(dead_code)]
+
+use std::{fs::{self, Permissions}, io::{Error, ErrorKind, SeekFrom}};
+
+use file_futures::{AsyncFile, FileKind, FileMetadata, LockMode};
+use futures::{task, Async, Poll};
+
+/// How many bytes a single `poll_read` or `poll_write` on a `MockFile` moves at most.
+pub const CHUNK: usize = 4;
+
+/// An `AsyncFile` that reports `NotReady` a fixed number of times before completing each
+/// operation, notifying the current task so executors keep polling it.
+///
+/// Reads and writes go to an in-memory buffer, at most `CHUNK` bytes at a time.
+#[derive(Debug)]
+pub struct MockFile {
+    not_ready: usize,
+    remaining: usize,
+    failing: bool,
+    pub polls: usize,
+    /// The operations that completed, in order.
+    pub calls: Vec<&'static str>,
+    pub data: Vec<u8>,
+    pub pos: usize,
+}
+
+impl MockFile {
+    pub fn new(not_ready: usize) -> Self {
+        MockFile {
+            not_ready,
+            remaining: not_ready,
+            failing: false,
+            polls: 0,
+            calls: Vec::new(),
+            data: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    pub fn with_data(not_ready: usize, data: &[u8]) -> Self {
+        MockFile {
+            data: data.to_vec(),
+            ..MockFile::new(not_ready)
+        }
+    }
+
+    /// A mock whose operations all fail with `PermissionDenied` once they're ready.
+    pub fn failing(not_ready: usize) -> Self {
+        MockFile {
+            failing: true,
+            ..MockFile::new(not_ready)
+        }
+    }
+
+    fn poll_op<T, F>(&mut self, name: &'static str, f: F) -> Poll<T, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Error>,
+    {
+        self.polls += 1;
+
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            task::current().notify();
+            return Ok(Async::NotReady);
+        }
+
+        self.remaining = self.not_ready;
+        self.calls.push(name);
+
+        if self.failing {
+            return Err(Error::new(ErrorKind::PermissionDenied, "mock failure"));
+        }
+
+        f(self).map(Async::Ready)
+    }
+
+    fn read_chunk(&self, buf: &mut [u8], pos: usize) -> usize {
+        let start = pos.min(self.data.len());
+        let n = CHUNK.min(buf.len()).min(self.data.len() - start);
+
+        buf[..n].copy_from_slice(&self.data[start..start + n]);
+        n
+    }
+
+    fn write_chunk(&mut self, buf: &[u8], pos: usize) -> usize {
+        let n = CHUNK.min(buf.len());
+
+        if self.data.len() < pos + n {
+            self.data.resize(pos + n, 0);
+        }
+
+        self.data[pos..pos + n].copy_from_slice(&buf[..n]);
+        n
+    }
+}
+
+fn manifest() -> String {
+    format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR"))
+}
+
+impl AsyncFile for MockFile {
+    type Cloned = tokio_fs::File;
+
+    fn poll_seek(&mut self, pos: SeekFrom) -> Poll<u64, Error> {
+        self.poll_op("seek", |file| {
+            if let SeekFrom::Start(pos) = pos {
+                file.pos = pos as usize;
+            }
+
+            Ok(file.pos as u64)
+        })
+    }
+
+    fn poll_sync_all(&mut self) -> Poll<(), Error> {
+        self.poll_op("sync_all", |_| Ok(()))
+    }
+
+    fn poll_sync_data(&mut self) -> Poll<(), Error> {
+        self.poll_op("sync_data", |_| Ok(()))
+    }
+
+    fn poll_set_len(&mut self, _size: u64) -> Poll<(), Error> {
+        self.poll_op("set_len", |_| Ok(()))
+    }
+
+    fn poll_metadata(&mut self) -> Poll<FileMetadata, Error> {
+        self.poll_op("metadata", |_| Ok(FileMetadata::new(FileKind::File, 0)))
+    }
+
+    fn poll_try_clone(&mut self) -> Poll<tokio_fs::File, Error> {
+        self.poll_op("try_clone", |_| fs::File::open(manifest()).map(tokio_fs::File::from_std))
+    }
+
+    fn poll_set_permissions(&mut self, _perm: Permissions) -> Poll<(), Error> {
+        self.poll_op("set_permissions", |_| Ok(()))
+    }
+
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+        self.poll_op("read", |file| {
+            let pos = file.pos;
+            let n = file.read_chunk(buf, pos);
+            file.pos += n;
+
+            Ok(n)
+        })
+    }
+
+    fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+        self.poll_op("write", |file| {
+            let pos = file.pos;
+            let n = file.write_chunk(buf, pos);
+            file.pos += n;
+
+            Ok(n)
+        })
+    }
+
+    fn poll_read_at(&mut self, buf: &mut [u8], offset: u64) -> Poll<usize, Error> {
+        self.poll_op("read_at", |file| Ok(file.read_chunk(buf, offset as usize)))
+    }
+
+    fn poll_write_at(&mut self, buf: &[u8], offset: u64) -> Poll<usize, Error> {
+        self.poll_op("write_at", |file| Ok(file.write_chunk(buf, offset as usize)))
+    }
+
+    fn poll_lock(&mut self, _mode: LockMode) -> Poll<(), Error> {
+        self.poll_op("lock", |_| Ok(()))
+    }
+
+    fn poll_try_lock(&mut self, _mode: LockMode) -> Poll<(), Error> {
+        self.poll_op("try_lock", |_| Ok(()))
+    }
+
+    fn poll_unlock(&mut self) -> Poll<(), Error> {
+        self.poll_op("unlock", |_| Ok(()))
+    }
+}
+
+pub fn permissions() -> Permissions {
+    fs::metadata(manifest()).unwrap().permissions()
+}
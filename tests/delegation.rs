@@ -0,0 +1,221 @@
+extern crate file_futures;
+extern crate futures;
+extern crate tokio_fs;
+
+mod common;
+
+use std::io::SeekFrom;
+
+use common::MockFile;
+use file_futures::{AsyncFile, LockMode, SyncPolicy};
+use futures::Future;
+
+const DELEGATED: &[&str] = &[
+    "seek",
+    "sync_all",
+    "sync_data",
+    "set_len",
+    "metadata",
+    "try_clone",
+    "set_permissions",
+    "read",
+    "write",
+    "read_at",
+    "write_at",
+    "lock",
+    "try_lock",
+    "unlock",
+];
+
+/// Call every `poll_*` method once, in the order of `DELEGATED`.
+fn exercise<F: AsyncFile>(file: &mut F) {
+    file.poll_seek(SeekFrom::Start(0)).unwrap();
+    file.poll_sync_all().unwrap();
+    file.poll_sync_data().unwrap();
+    file.poll_set_len(0).unwrap();
+    file.poll_metadata().unwrap();
+    file.poll_try_clone().unwrap();
+    file.poll_set_permissions(common::permissions()).unwrap();
+    file.poll_read(&mut [0; 4]).unwrap();
+    file.poll_write(&[0; 4]).unwrap();
+    file.poll_read_at(&mut [0; 4], 0).unwrap();
+    file.poll_write_at(&[0; 4], 0).unwrap();
+    file.poll_lock(LockMode::Shared).unwrap();
+    file.poll_try_lock(LockMode::Exclusive).unwrap();
+    file.poll_unlock().unwrap();
+}
+
+fn expected(own: &[&'static str]) -> Vec<&'static str> {
+    DELEGATED.iter().chain(own).cloned().collect()
+}
+
+#[test]
+fn seek_delegates() {
+    let mut future = MockFile::new(0).seek(SeekFrom::Start(0));
+    exercise(&mut future);
+
+    let (file, _) = future.wait().unwrap();
+    assert_eq!(file.calls, expected(&["seek"]));
+}
+
+#[test]
+fn sync_all_delegates() {
+    let mut future = MockFile::new(0).sync_all();
+    exercise(&mut future);
+
+    assert_eq!(future.wait().unwrap().calls, expected(&["sync_all"]));
+}
+
+#[test]
+fn sync_data_delegates() {
+    let mut future = MockFile::new(0).sync_data();
+    exercise(&mut future);
+
+    assert_eq!(future.wait().unwrap().calls, expected(&["sync_data"]));
+}
+
+#[test]
+fn set_len_delegates() {
+    let mut future = MockFile::new(0).set_len(30);
+    exercise(&mut future);
+
+    assert_eq!(future.wait().unwrap().calls, expected(&["set_len"]));
+}
+
+#[test]
+fn metadata_delegates() {
+    let mut future = MockFile::new(0).metadata();
+    exercise(&mut future);
+
+    let (file, _) = future.wait().unwrap();
+    assert_eq!(file.calls, expected(&["metadata"]));
+}
+
+#[test]
+fn try_clone_delegates() {
+    let mut future = MockFile::new(0).try_clone();
+    exercise(&mut future);
+
+    let (file, _) = future.wait().unwrap();
+    assert_eq!(file.calls, expected(&["try_clone"]));
+}
+
+#[test]
+fn set_permissions_delegates() {
+    let mut future = MockFile::new(0).set_permissions(common::permissions());
+    exercise(&mut future);
+
+    assert_eq!(future.wait().unwrap().calls, expected(&["set_permissions"]));
+}
+
+#[test]
+fn sync_with_delegates() {
+    let mut future = MockFile::new(0).sync_with(SyncPolicy::All);
+    exercise(&mut future);
+
+    assert_eq!(future.wait().unwrap().calls, expected(&["sync_all"]));
+}
+
+#[test]
+fn read_delegates() {
+    let mut future = MockFile::new(0).read(vec![0; 4]);
+    exercise(&mut future);
+
+    let (file, _, _) = future.wait().unwrap();
+    assert_eq!(file.calls, expected(&["read"]));
+}
+
+#[test]
+fn write_delegates() {
+    let mut future = MockFile::new(0).write(vec![0; 4]);
+    exercise(&mut future);
+
+    let (file, _, _) = future.wait().unwrap();
+    assert_eq!(file.calls, expected(&["write"]));
+}
+
+#[test]
+fn read_exact_delegates() {
+    let mut future = MockFile::with_data(0, &[0; 16]).read_exact(vec![0; 4]);
+    exercise(&mut future);
+
+    let (file, _, _) = future.wait().unwrap();
+    assert_eq!(file.calls, expected(&["read"]));
+}
+
+#[test]
+fn write_all_delegates() {
+    let mut future = MockFile::new(0).write_all(vec![0; 4]);
+    exercise(&mut future);
+
+    let (file, _, _) = future.wait().unwrap();
+    assert_eq!(file.calls, expected(&["write"]));
+}
+
+#[test]
+fn read_at_delegates() {
+    let mut future = MockFile::new(0).read_at(0, vec![0; 4]);
+    exercise(&mut future);
+
+    let (file, _, _) = future.wait().unwrap();
+    assert_eq!(file.calls, expected(&["read_at"]));
+}
+
+#[test]
+fn write_at_delegates() {
+    let mut future = MockFile::new(0).write_at(0, vec![0; 4]);
+    exercise(&mut future);
+
+    let (file, _, _) = future.wait().unwrap();
+    assert_eq!(file.calls, expected(&["write_at"]));
+}
+
+#[test]
+fn lock_delegates() {
+    let mut future = MockFile::new(0).lock_exclusive();
+    exercise(&mut future);
+
+    let mut locked = future.wait().unwrap();
+    exercise(&mut locked);
+
+    let file = locked.unlock().wait().unwrap();
+    let mut own = vec!["lock"];
+    own.extend(DELEGATED);
+    own.push("unlock");
+    assert_eq!(file.calls, expected(&own));
+}
+
+#[test]
+fn unlock_delegates() {
+    let mut future = MockFile::new(0).unlock();
+    exercise(&mut future);
+
+    assert_eq!(future.wait().unwrap().calls, expected(&["unlock"]));
+}
+
+#[test]
+fn nested_futures_delegate() {
+    let mut future = MockFile::new(0).sync_all().set_len(30);
+    exercise(&mut future);
+
+    let file = future.wait().unwrap().wait().unwrap();
+    assert_eq!(file.calls, expected(&["set_len", "sync_all"]));
+}
+
+#[test]
+fn borrowed_files_delegate() {
+    let mut file = MockFile::new(0);
+    exercise(&mut &mut file);
+
+    (&mut file).sync_all().wait().unwrap();
+    assert_eq!(file.calls, expected(&["sync_all"]));
+}
+
+#[test]
+fn boxed_files_delegate() {
+    let mut file = Box::new(MockFile::new(0));
+    exercise(&mut file);
+
+    let file = file.sync_all().wait().unwrap();
+    assert_eq!(file.calls, expected(&["sync_all"]));
+}
@@ -0,0 +1,52 @@
+extern crate file_futures;
+extern crate futures;
+
+use std::io::SeekFrom;
+
+use file_futures::{AsyncFile, DynAsyncFile, FaultPlan, FaultyFile, MemFile};
+use futures::Future;
+
+fn boxed(faulty: bool) -> Box<dyn DynAsyncFile> {
+    let file = MemFile::with_contents("hello world");
+
+    if faulty {
+        Box::new(FaultyFile::new(file, FaultPlan::new(0)))
+    } else {
+        Box::new(file)
+    }
+}
+
+#[test]
+fn boxed_files_run_the_usual_futures() {
+    for &faulty in &[false, true] {
+        let (file, contents) = boxed(faulty)
+            .seek(SeekFrom::Start(6))
+            .and_then(|(file, _)| file.read_to_string(64))
+            .wait()
+            .unwrap();
+
+        assert_eq!(contents, "world");
+
+        let (_, metadata) = file.set_len(5).and_then(|file| file.metadata()).wait().unwrap();
+        assert_eq!(metadata.len(), 5);
+    }
+}
+
+#[test]
+fn clones_of_boxed_files_are_boxed() {
+    let (file, clone) = boxed(false).try_clone().wait().unwrap();
+
+    let clone = clone.write_all(b"HELLO").wait().unwrap().0;
+    let (_, clone) = clone.try_clone().wait().unwrap();
+
+    let (_, contents) = file
+        .seek(SeekFrom::Start(0))
+        .and_then(|(file, _)| file.read_to_string(64))
+        .wait()
+        .unwrap();
+    assert_eq!(contents, "HELLO world");
+
+    let boxed_again: Box<dyn DynAsyncFile> = Box::new(clone);
+    let guard = boxed_again.lock_exclusive().wait().unwrap();
+    guard.unlock().wait().unwrap();
+}
@@ -0,0 +1,43 @@
+extern crate file_futures;
+extern crate futures;
+extern crate tokio_fs;
+
+mod common;
+
+use std::io::{self, ErrorKind, SeekFrom};
+
+use common::MockFile;
+use file_futures::AsyncFile;
+use futures::Future;
+
+#[test]
+fn errors_return_the_file() {
+    let err = MockFile::failing(2).set_len(30).wait().unwrap_err();
+
+    assert_eq!(err.operation(), "set_len");
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    assert_eq!(err.file().polls, 3);
+}
+
+#[test]
+fn recovered_file_is_usable() {
+    let err = MockFile::failing(0)
+        .set_permissions(common::permissions())
+        .wait()
+        .unwrap_err();
+
+    assert_eq!(err.operation(), "set_permissions");
+
+    let file = err.into_file();
+    let err = file.seek(SeekFrom::Start(3)).wait().unwrap_err();
+
+    assert_eq!(err.operation(), "seek");
+    assert_eq!(err.file().polls, 2);
+}
+
+#[test]
+fn converts_into_io_error() {
+    let err: io::Error = MockFile::failing(0).sync_data().wait().unwrap_err().into();
+
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+}
@@ -0,0 +1,125 @@
+extern crate file_futures;
+extern crate futures;
+
+use std::{
+    io::{ErrorKind, SeekFrom},
+    time::{Duration, Instant},
+};
+
+use file_futures::{AsyncFile, Fault, FaultPlan, FaultyFile, FileOp, MemFile};
+use futures::{future, Future};
+
+#[test]
+fn short_writes_then_a_full_disk() {
+    let plan = FaultPlan::new(0)
+        .on_call(FileOp::Write, 0, Fault::ShortWrite(2))
+        .on_call(FileOp::Write, 1, Fault::Enospc);
+
+    let err = FaultyFile::new(MemFile::new(), plan)
+        .write_all(b"abcdefgh")
+        .wait()
+        .err()
+        .unwrap();
+
+    assert_eq!(err.operation(), "write_all");
+    #[cfg(unix)]
+    assert_eq!(err.error().raw_os_error(), Some(28));
+
+    let file = err.into_file();
+    assert_eq!(file.calls(FileOp::Write), 2);
+    assert_eq!(file.get_ref().contents(), b"ab");
+}
+
+#[test]
+fn failed_operations_skip_the_file() {
+    let denied = Fault::Error(ErrorKind::PermissionDenied);
+    let plan = FaultPlan::new(0).on_call(FileOp::SetLen, 0, denied);
+
+    let err = FaultyFile::new(MemFile::with_contents("abc"), plan)
+        .set_len(0)
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+    let file = err.into_file().set_len(1).wait().unwrap();
+    assert_eq!(file.get_ref().contents(), b"a");
+}
+
+#[test]
+fn delays_and_spurious_not_ready_still_complete() {
+    let plan = FaultPlan::new(0)
+        .on_call(FileOp::SyncAll, 0, Fault::Delay(Duration::from_millis(20)))
+        .with_probability(FileOp::Read, 1.0, Fault::NotReady);
+
+    let start = Instant::now();
+    let (_, contents) = FaultyFile::new(MemFile::with_contents("abc"), plan)
+        .sync_all()
+        .and_then(|file| file.read_to_string(64))
+        .wait()
+        .unwrap();
+
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert_eq!(contents, "abc");
+}
+
+fn seek_results(seed: u64) -> Vec<bool> {
+    let plan = FaultPlan::new(seed).with_probability(FileOp::Seek, 0.5, Fault::Eio);
+    let mut file = FaultyFile::new(MemFile::new(), plan);
+    let mut results = Vec::new();
+
+    for _ in 0..32 {
+        file = match file.seek(SeekFrom::Start(0)).wait() {
+            Ok((file, _)) => {
+                results.push(true);
+                file
+            }
+            Err(e) => {
+                results.push(false);
+                e.into_file()
+            }
+        };
+    }
+
+    results
+}
+
+#[test]
+fn probabilities_are_seeded() {
+    let results = seek_results(7);
+
+    assert_eq!(results, seek_results(7));
+    assert_ne!(results, seek_results(8));
+    assert!(results.contains(&true) && results.contains(&false));
+}
+
+#[test]
+fn clones_share_the_plan() {
+    let plan = FaultPlan::new(0).on_call(FileOp::SyncData, 1, Fault::Eio);
+
+    let (file, clone) = FaultyFile::new(MemFile::new(), plan)
+        .try_clone()
+        .wait()
+        .unwrap();
+
+    let file = file.sync_data().wait().unwrap();
+    let err = clone.sync_data().wait().unwrap_err();
+
+    assert_eq!(err.operation(), "sync_data");
+    assert_eq!(file.calls(FileOp::SyncData), 2);
+}
+
+#[test]
+fn abandoned_operations_leave_no_delay_behind() {
+    let plan = FaultPlan::new(0).on_call(FileOp::Seek, 0, Fault::Delay(Duration::from_secs(60)));
+    let mut seek = FaultyFile::new(MemFile::new(), plan).seek(SeekFrom::Start(0));
+
+    future::lazy(move || {
+        assert!(seek.poll().unwrap().is_not_ready());
+
+        let mut seek = seek.into_inner().seek(SeekFrom::Start(0));
+        assert!(seek.poll().unwrap().is_ready());
+        Ok::<_, ()>(())
+    })
+    .wait()
+    .unwrap();
+}
@@ -0,0 +1,40 @@
+extern crate file_futures;
+extern crate futures;
+extern crate tokio;
+
+use std::{env, io::ErrorKind};
+
+use file_futures::SyncPolicy;
+use futures::Future;
+
+#[test]
+fn write_append_read() {
+    let path = env::temp_dir().join("file-futures-path-futures");
+    std::fs::write(&path, "some much longer previous contents").unwrap();
+
+    let future = file_futures::write(path.clone(), "hello")
+        .and_then({
+            let path = path.clone();
+            move |_| file_futures::append(path, b" world".to_vec()).sync(SyncPolicy::Data)
+        })
+        .and_then(move |_| file_futures::read(path));
+
+    let contents = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(future)
+        .unwrap();
+
+    assert_eq!(contents, b"hello world");
+}
+
+#[test]
+fn read_missing_file() {
+    let path = env::temp_dir().join("file-futures-path-futures-missing");
+
+    let err = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(file_futures::read(path))
+        .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
@@ -0,0 +1,100 @@
+extern crate file_futures;
+extern crate futures;
+extern crate tokio;
+extern crate tokio_fs;
+
+mod common;
+
+use std::{env, io::{self, ErrorKind}, path::PathBuf};
+
+use common::MockFile;
+use file_futures::{AsyncFile, LockMode};
+use futures::Future;
+use tokio_fs::file::OpenFuture;
+
+fn open(name: &str) -> OpenFuture<PathBuf> {
+    std::fs::write(env::temp_dir().join(name), "").unwrap();
+
+    reopen(name)
+}
+
+/// Open the file again, as a separate open file description that contends for locks.
+fn reopen(name: &str) -> OpenFuture<PathBuf> {
+    tokio_fs::File::open(env::temp_dir().join(name))
+}
+
+#[test]
+fn exclusive_locks_contend() {
+    let name = "file-futures-lock-exclusive";
+
+    let future = open(name)
+        .and_then(|file| file.lock_exclusive().map_err(Into::into))
+        .and_then(move |locked| {
+            assert_eq!(locked.mode(), LockMode::Exclusive);
+
+            reopen(name)
+                .and_then(|other| other.try_lock_shared().then(Ok))
+                .map(move |res| (locked, res))
+        })
+        .and_then(|(locked, res)| {
+            let err = res.err().unwrap();
+            assert_eq!(err.operation(), "try_lock");
+            assert_eq!(err.kind(), ErrorKind::WouldBlock);
+
+            let other = err.into_file();
+            locked
+                .unlock()
+                .map_err(Into::into)
+                .and_then(|_| other.try_lock_exclusive().map_err(Into::into))
+        })
+        .map(|_| ());
+
+    let res: Result<(), io::Error> = tokio::runtime::Runtime::new().unwrap().block_on(future);
+    res.unwrap();
+}
+
+#[test]
+fn shared_locks_coexist() {
+    let name = "file-futures-lock-shared";
+
+    let future = open(name)
+        .and_then(|file| file.lock_shared().map_err(Into::into))
+        .and_then(move |locked| {
+            reopen(name)
+                .and_then(|other| other.try_lock_shared().map_err(Into::into))
+                .map(move |other| (locked, other))
+        })
+        .and_then(|(_, other)| other.unlock().map_err(Into::into))
+        .map(|_| ());
+
+    let res: Result<(), io::Error> = tokio::runtime::Runtime::new().unwrap().block_on(future);
+    res.unwrap();
+}
+
+#[test]
+fn dropping_releases_the_lock() {
+    let name = "file-futures-lock-drop";
+
+    let future = open(name)
+        .and_then(|file| file.try_lock_exclusive().map_err(Into::into))
+        .and_then(move |locked| {
+            drop(locked);
+
+            reopen(name).and_then(|other| other.try_lock_exclusive().map_err(Into::into))
+        })
+        .map(|_| ());
+
+    let res: Result<(), io::Error> = tokio::runtime::Runtime::new().unwrap().block_on(future);
+    res.unwrap();
+}
+
+#[test]
+fn locked_file_survives_not_ready() {
+    let file = MockFile::new(2)
+        .try_lock_shared()
+        .and_then(|locked| locked.unlock())
+        .wait()
+        .unwrap();
+
+    assert_eq!(file.calls, vec!["try_lock", "unlock"]);
+}
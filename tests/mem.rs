@@ -0,0 +1,114 @@
+extern crate file_futures;
+extern crate futures;
+
+use std::{
+    fs,
+    io::{ErrorKind, SeekFrom},
+};
+
+use file_futures::{AsyncFile, FileKind, LockMode, MemFile};
+use futures::Future;
+
+#[test]
+fn writes_past_the_end_fill_with_zeros() {
+    let (file, _, _) = MemFile::with_contents("abc")
+        .seek(SeekFrom::End(2))
+        .and_then(|(file, _)| file.write_all(b"de"))
+        .wait()
+        .unwrap();
+
+    assert_eq!(file.contents(), b"abc\0\0de");
+}
+
+#[test]
+fn set_len_leaves_the_cursor() {
+    let (file, n) = MemFile::with_contents("hello world")
+        .seek(SeekFrom::Start(8))
+        .and_then(|(file, _)| file.set_len(5))
+        .and_then(|file| file.seek(SeekFrom::Current(0)))
+        .wait()
+        .unwrap();
+
+    assert_eq!(n, 8);
+    assert_eq!(file.contents(), b"hello");
+
+    let (_, buf, n) = file.read(vec![0; 4]).wait().unwrap();
+    assert_eq!(n, 0);
+    assert_eq!(buf, [0; 4]);
+}
+
+#[test]
+fn seeking_before_the_start_fails() {
+    let err = MemFile::with_contents("abc")
+        .seek(SeekFrom::End(-4))
+        .wait()
+        .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    let (_, n) = err.into_file().seek(SeekFrom::Current(0)).wait().unwrap();
+    assert_eq!(n, 0);
+}
+
+#[test]
+fn clones_share_the_cursor_and_reopening_does_not() {
+    let file = MemFile::with_contents("hello world");
+    let reopened = file.reopen();
+
+    let (file, clone) = file
+        .seek(SeekFrom::Start(6))
+        .and_then(|(file, _)| file.try_clone())
+        .wait()
+        .unwrap();
+
+    let (_, contents) = clone.read_to_string(64).wait().unwrap();
+    assert_eq!(contents, "world");
+
+    let (_, contents) = reopened.read_to_string(64).wait().unwrap();
+    assert_eq!(contents, "hello world");
+
+    let (_, n) = file.seek(SeekFrom::Current(0)).wait().unwrap();
+    assert_eq!(n, 11);
+}
+
+#[test]
+fn locks_contend_between_reopened_handles() {
+    let file = MemFile::new();
+    let other = file.reopen();
+
+    let locked = file.lock_exclusive().wait().unwrap();
+    assert_eq!(locked.mode(), LockMode::Exclusive);
+
+    let err = other.try_lock_shared().wait().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::WouldBlock);
+
+    // The waiting lock is woken by the unlock.
+    let (locked, file) = err
+        .into_file()
+        .lock_shared()
+        .join(locked.unlock())
+        .wait()
+        .unwrap();
+    assert_eq!(locked.mode(), LockMode::Shared);
+
+    drop(locked);
+    file.try_lock_exclusive().wait().unwrap();
+}
+
+#[test]
+fn metadata_tracks_length_and_permissions() {
+    let mut readonly = fs::metadata(env!("CARGO_MANIFEST_DIR")).unwrap().permissions();
+    readonly.set_readonly(true);
+
+    let (_, metadata) = MemFile::with_contents("abc")
+        .set_len(10)
+        .and_then(|file| file.set_permissions(readonly))
+        .and_then(|file| file.metadata())
+        .wait()
+        .unwrap();
+
+    assert_eq!(metadata.len(), 10);
+    assert_eq!(metadata.kind(), FileKind::File);
+    assert!(metadata.readonly());
+    assert!(metadata.modified().is_some());
+}
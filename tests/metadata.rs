@@ -0,0 +1,71 @@
+extern crate file_futures;
+extern crate futures;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+
+use std::{env, fs};
+
+use file_futures::{AsyncFile, FileKind, FileMetadata, PooledFile};
+use futures::Future;
+
+#[test]
+fn converts_from_std() {
+    let path = env::temp_dir().join("file-futures-metadata-std");
+    fs::write(&path, "contents").unwrap();
+
+    let std = fs::metadata(&path).unwrap();
+    let (_, metadata) = PooledFile::new(fs::File::open(&path).unwrap())
+        .metadata()
+        .wait()
+        .unwrap();
+
+    assert_eq!(metadata.len(), 8);
+    assert_eq!(metadata.kind(), FileKind::File);
+    assert_eq!(metadata.permissions(), Some(std.permissions()));
+    assert_eq!(metadata.modified(), std.modified().ok());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        assert_eq!(metadata.mode(), Some(std.mode()));
+        assert_eq!(metadata.uid(), Some(std.uid()));
+        assert_eq!(metadata.ino(), Some(std.ino()));
+        assert_eq!(metadata.nlink(), Some(1));
+        assert!(metadata.changed().is_some());
+    }
+}
+
+#[test]
+fn builds_by_hand() {
+    let metadata = FileMetadata::new(FileKind::Dir, 0).with_owner(1000, 100);
+
+    assert!(metadata.is_dir());
+    assert!(!metadata.readonly());
+    assert_eq!(metadata.uid(), Some(1000));
+    assert_eq!(metadata.permissions(), None);
+    assert_eq!(metadata.created(), None);
+
+    let metadata = metadata.with_mode(0o40555);
+    assert!(metadata.readonly());
+
+    #[cfg(unix)]
+    assert!(metadata.permissions().unwrap().readonly());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn round_trips_through_serde() {
+    let path = env::temp_dir().join("file-futures-metadata-serde");
+    fs::write(&path, "contents").unwrap();
+    let metadata = FileMetadata::from(fs::metadata(&path).unwrap());
+
+    let json = serde_json::to_string(&metadata).unwrap();
+    let parsed: FileMetadata = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(parsed, metadata);
+    assert_eq!(parsed.readonly(), metadata.readonly());
+
+    #[cfg(unix)]
+    assert_eq!(parsed.permissions(), metadata.permissions());
+}
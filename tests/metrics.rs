@@ -0,0 +1,126 @@
+extern crate file_futures;
+extern crate futures;
+
+use std::{
+    io::{Error, ErrorKind, SeekFrom},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use file_futures::{
+    AsyncFile, Fault, FaultPlan, FaultyFile, FileMetrics, FileOp, Histogram, InstrumentedFile,
+    MemFile, MemMetrics,
+};
+use futures::{future, Future};
+
+#[test]
+fn counts_calls_and_errors() {
+    let plan = FaultPlan::new(0).on_call(FileOp::SetLen, 1, Fault::Eio);
+    let metrics = Arc::new(MemMetrics::new());
+    let file = InstrumentedFile::new(FaultyFile::new(MemFile::new(), plan), metrics.clone());
+
+    let err = file
+        .set_len(3)
+        .and_then(|file| file.sync_all())
+        .and_then(|file| file.set_len(1))
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.operation(), "set_len");
+
+    assert_eq!(metrics.calls(FileOp::SetLen), 2);
+    assert_eq!(metrics.errors(FileOp::SetLen), 1);
+    assert_eq!(metrics.calls(FileOp::SyncAll), 1);
+    assert_eq!(metrics.errors(FileOp::SyncAll), 0);
+    assert_eq!(metrics.calls(FileOp::Seek), 0);
+}
+
+#[test]
+fn times_operations_from_their_first_poll() {
+    let delay = Fault::Delay(Duration::from_millis(20));
+    let plan = FaultPlan::new(0).on_call(FileOp::SyncData, 0, delay);
+    let metrics = Arc::new(MemMetrics::new());
+    let file = InstrumentedFile::new(FaultyFile::new(MemFile::new(), plan), metrics.clone());
+
+    let (file, clone) = file.sync_data().wait().unwrap().try_clone().wait().unwrap();
+    clone.sync_data().wait().unwrap();
+
+    let latency = metrics.latency(FileOp::SyncData);
+    assert_eq!(latency.count(), 2);
+    assert!(latency.max().unwrap() >= Duration::from_millis(20));
+    assert!(latency.quantile(1.0).unwrap() >= Duration::from_millis(20));
+    assert!(latency.quantile(0.5).unwrap() < Duration::from_millis(20));
+    assert_eq!(file.metrics().calls(FileOp::TryClone), 1);
+}
+
+#[test]
+fn abandoned_operations_leave_no_start_time_behind() {
+    let plan = FaultPlan::new(0).on_call(FileOp::Seek, 0, Fault::NotReady);
+    let metrics = Arc::new(MemMetrics::new());
+    let file = InstrumentedFile::new(FaultyFile::new(MemFile::new(), plan), metrics.clone());
+    let mut seek = file.seek(SeekFrom::Start(1));
+
+    future::lazy(move || {
+        assert!(seek.poll().unwrap().is_not_ready());
+        thread::sleep(Duration::from_millis(20));
+
+        let mut seek = seek.into_inner().seek(SeekFrom::Start(2));
+        assert!(seek.poll().unwrap().is_ready());
+        Ok::<_, ()>(())
+    })
+    .wait()
+    .unwrap();
+
+    let latency = metrics.latency(FileOp::Seek);
+    assert_eq!(latency.count(), 1);
+    assert!(latency.max().unwrap() < Duration::from_millis(20));
+}
+
+#[test]
+fn histograms_bucket_by_powers_of_two() {
+    let mut histogram = Histogram::new();
+    assert_eq!(histogram.mean(), None);
+    assert_eq!(histogram.quantile(0.5), None);
+
+    histogram.record(Duration::from_micros(3));
+    histogram.record(Duration::from_micros(4));
+    histogram.record(Duration::from_micros(5));
+    histogram.record(Duration::from_secs(60));
+
+    let buckets = histogram.buckets();
+    assert_eq!(buckets[2], (Duration::from_micros(4), 2));
+    assert_eq!(buckets[3], (Duration::from_micros(8), 1));
+    assert_eq!(buckets.last(), Some(&(Duration::MAX, 1)));
+
+    assert_eq!(histogram.count(), 4);
+    assert_eq!(histogram.min(), Some(Duration::from_micros(3)));
+    assert_eq!(histogram.quantile(0.5), Some(Duration::from_micros(4)));
+    assert_eq!(histogram.quantile(1.0), Some(Duration::from_secs(60)));
+}
+
+/// Collects the name of each failed operation, as a metrics adapter might.
+#[derive(Debug, Default)]
+struct Failures(Mutex<Vec<String>>);
+
+impl FileMetrics for Failures {
+    fn record(&self, op: FileOp, _elapsed: Duration, error: Option<&Error>) {
+        if let Some(e) = error {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{}: {:?}", op, e.kind()));
+        }
+    }
+}
+
+#[test]
+fn reports_to_custom_metrics() {
+    let denied = Fault::Error(ErrorKind::PermissionDenied);
+    let plan = FaultPlan::new(0).on_call(FileOp::Metadata, 0, denied);
+    let failures = Arc::new(Failures::default());
+    let file = InstrumentedFile::new(FaultyFile::new(MemFile::new(), plan), failures.clone());
+
+    file.metadata().wait().unwrap_err();
+
+    assert_eq!(*failures.0.lock().unwrap(), ["metadata: PermissionDenied"]);
+}
@@ -0,0 +1,84 @@
+extern crate file_futures;
+extern crate futures;
+
+use std::{
+    error::Error as StdError,
+    io::{ErrorKind, SeekFrom},
+    path::Path,
+};
+
+use file_futures::{
+    AsyncFile, Fault, FaultPlan, FaultyFile, FileOp, MemFile, NamedFile, PathError,
+};
+use futures::Future;
+
+fn named(plan: FaultPlan) -> NamedFile<FaultyFile<MemFile>> {
+    NamedFile::new(
+        FaultyFile::new(MemFile::with_contents("hello"), plan),
+        "/var/data/x",
+    )
+}
+
+#[test]
+fn errors_name_the_call_and_path() {
+    let denied = Fault::Error(ErrorKind::PermissionDenied);
+    let plan = FaultPlan::new(0).on_call(FileOp::SetLen, 0, denied);
+
+    let err = named(plan).set_len(30).wait().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    assert_eq!(err.operation(), "set_len");
+
+    let inner = err
+        .error()
+        .get_ref()
+        .unwrap()
+        .downcast_ref::<PathError>()
+        .unwrap();
+    assert_eq!(inner.call(), "set_len(30)");
+    assert_eq!(inner.path(), Path::new("/var/data/x"));
+    assert_eq!(
+        err.error().to_string(),
+        "set_len(30) on /var/data/x: injected fault"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn the_original_error_is_the_source() {
+    let plan = FaultPlan::new(0).on_call(FileOp::Seek, 0, Fault::Eio);
+
+    let err = named(plan).seek(SeekFrom::Start(2)).wait().err().unwrap();
+    let source = err.error().source().unwrap();
+
+    assert!(err
+        .error()
+        .to_string()
+        .starts_with("seek(Start(2)) on /var/data/x: "));
+    assert_eq!(
+        source
+            .downcast_ref::<std::io::Error>()
+            .unwrap()
+            .raw_os_error(),
+        Some(5)
+    );
+}
+
+#[test]
+fn successful_calls_pass_through() {
+    let (file, contents) = named(FaultPlan::new(0))
+        .seek(SeekFrom::Start(1))
+        .and_then(|(file, _)| file.read_to_string(64))
+        .wait()
+        .unwrap();
+
+    assert_eq!(contents, "ello");
+    assert_eq!(file.path(), Some(Path::new("/var/data/x")));
+    assert_eq!(file.into_inner().into_inner().contents(), b"hello");
+}
+
+#[test]
+fn clones_keep_the_path() {
+    let (file, clone) = named(FaultPlan::new(0)).try_clone().wait().unwrap();
+
+    assert_eq!(clone.path(), file.path());
+}
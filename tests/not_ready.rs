@@ -0,0 +1,82 @@
+extern crate file_futures;
+extern crate futures;
+extern crate tokio_fs;
+
+mod common;
+
+use std::io::SeekFrom;
+
+use common::MockFile;
+use file_futures::AsyncFile;
+use futures::Future;
+
+const NOT_READY: usize = 3;
+
+#[test]
+fn seek_survives_not_ready() {
+    let (file, pos) = MockFile::new(NOT_READY)
+        .seek(SeekFrom::Start(30))
+        .wait()
+        .unwrap();
+
+    assert_eq!(pos, 30);
+    assert_eq!(file.polls, NOT_READY + 1);
+}
+
+#[test]
+fn sync_all_survives_not_ready() {
+    let file = MockFile::new(NOT_READY).sync_all().wait().unwrap();
+
+    assert_eq!(file.polls, NOT_READY + 1);
+}
+
+#[test]
+fn sync_data_survives_not_ready() {
+    let file = MockFile::new(NOT_READY).sync_data().wait().unwrap();
+
+    assert_eq!(file.polls, NOT_READY + 1);
+}
+
+#[test]
+fn set_len_survives_not_ready() {
+    let file = MockFile::new(NOT_READY).set_len(30).wait().unwrap();
+
+    assert_eq!(file.polls, NOT_READY + 1);
+}
+
+#[test]
+fn metadata_survives_not_ready() {
+    let (file, metadata) = MockFile::new(NOT_READY).metadata().wait().unwrap();
+
+    assert!(metadata.is_file());
+    assert_eq!(file.polls, NOT_READY + 1);
+}
+
+#[test]
+fn try_clone_survives_not_ready() {
+    let (file, _clone) = MockFile::new(NOT_READY).try_clone().wait().unwrap();
+
+    assert_eq!(file.polls, NOT_READY + 1);
+}
+
+#[test]
+fn set_permissions_survives_not_ready() {
+    let file = MockFile::new(NOT_READY)
+        .set_permissions(common::permissions())
+        .wait()
+        .unwrap();
+
+    assert_eq!(file.polls, NOT_READY + 1);
+}
+
+#[test]
+fn chained_futures_survive_not_ready() {
+    let file = MockFile::new(NOT_READY)
+        .set_len(30)
+        .and_then(|file| file.seek(SeekFrom::Start(10)))
+        .and_then(|(file, _)| file.sync_all())
+        .wait()
+        .unwrap();
+
+    assert_eq!(file.polls, 3 * (NOT_READY + 1));
+}
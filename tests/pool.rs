@@ -0,0 +1,110 @@
+extern crate file_futures;
+extern crate futures;
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::SeekFrom,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use file_futures::{AsyncFile, Pool, PooledFile};
+use futures::Future;
+
+fn open(name: &str) -> fs::File {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(env::temp_dir().join(name))
+        .unwrap()
+}
+
+#[test]
+fn runs_without_a_runtime() {
+    let file = PooledFile::new(open("file-futures-pool-round-trip"));
+
+    let (metadata, buf) = file
+        .write_all(b"hello world")
+        .and_then(|(file, _, _)| file.seek(SeekFrom::Start(6)))
+        .and_then(|(file, _)| file.read_exact(vec![0; 5]))
+        .and_then(|(file, buf, _)| file.set_len(5).map(|file| (file, buf)))
+        .and_then(|(file, buf)| file.metadata().map(|(_, metadata)| (metadata, buf)))
+        .wait()
+        .unwrap();
+
+    assert_eq!(buf, b"world");
+    assert_eq!(metadata.len(), 5);
+}
+
+#[test]
+fn clones_share_the_pool() {
+    let file = PooledFile::with_pool(open("file-futures-pool-clone"), Pool::new(1));
+
+    let (_, buf, n) = file
+        .write_at(2, b"ab")
+        .and_then(|(file, _, _)| file.try_clone())
+        .and_then(|(_, clone)| clone.read_at(0, vec![1; 4]))
+        .wait()
+        .unwrap();
+
+    assert_eq!(n, 4);
+    assert_eq!(buf, b"\0\0ab");
+}
+
+#[test]
+fn locks_release_outside_a_task() {
+    let file = PooledFile::new(open("file-futures-pool-lock"));
+
+    let locked = file.lock_exclusive().wait().unwrap();
+    drop(locked);
+
+    let other = PooledFile::new(open("file-futures-pool-lock"));
+    other.lock_exclusive().wait().unwrap();
+}
+
+#[test]
+fn waiting_for_locks_leaves_the_pool_free() {
+    let name = "file-futures-pool-lock-waiters";
+    let pool = Pool::new(2);
+
+    let locked = PooledFile::with_pool(open(name), pool.clone())
+        .lock_exclusive()
+        .wait()
+        .unwrap();
+
+    // As many waiters as there are threads in the pool.
+    let waiters: Vec<_> = (0..2)
+        .map(|_| {
+            let file = PooledFile::with_pool(open(name), pool.clone());
+
+            thread::spawn(move || file.lock_exclusive().and_then(|l| l.unlock()).wait())
+        })
+        .collect();
+
+    thread::sleep(Duration::from_millis(100));
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || tx.send(locked.unlock().wait().map(|_| ())));
+    rx.recv_timeout(Duration::from_secs(3)).unwrap().unwrap();
+
+    for waiter in waiters {
+        waiter.join().unwrap().unwrap();
+    }
+}
+
+#[test]
+fn errors_return_the_file() {
+    let path = env::temp_dir().join("file-futures-pool-errors");
+    fs::write(&path, "contents").unwrap();
+    let file = PooledFile::new(fs::File::open(&path).unwrap());
+
+    let err = file.set_len(0).wait().unwrap_err();
+    assert_eq!(err.operation(), "set_len");
+
+    let (_, contents) = err.into_file().read_to_string(64).wait().unwrap();
+    assert_eq!(contents, "contents");
+}
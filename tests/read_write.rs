@@ -0,0 +1,220 @@
+extern crate file_futures;
+extern crate futures;
+extern crate tokio;
+extern crate tokio_fs;
+
+mod common;
+
+use std::{env, io::{self, ErrorKind, SeekFrom}};
+
+use common::{MockFile, CHUNK};
+use file_futures::{AsyncFile, LimitExceeded, MemFile};
+use futures::Future;
+
+const NOT_READY: usize = 2;
+
+#[test]
+fn read_returns_the_buffer() {
+    let (file, buf, n) = MockFile::with_data(NOT_READY, b"hello")
+        .read(vec![0; 16])
+        .wait()
+        .unwrap();
+
+    assert_eq!(n, CHUNK);
+    assert_eq!(&buf[..n], b"hell");
+    assert_eq!(file.polls, NOT_READY + 1);
+}
+
+#[test]
+fn write_returns_the_buffer() {
+    let (file, buf, n) = MockFile::new(NOT_READY)
+        .write(b"hello")
+        .wait()
+        .unwrap();
+
+    assert_eq!(n, CHUNK);
+    assert_eq!(buf, b"hello");
+    assert_eq!(file.data, b"hell");
+}
+
+#[test]
+fn read_exact_fills_the_buffer() {
+    let (file, buf, n) = MockFile::with_data(NOT_READY, b"hello world")
+        .read_exact([0; 11])
+        .wait()
+        .unwrap();
+
+    assert_eq!(n, 11);
+    assert_eq!(&buf, b"hello world");
+    assert_eq!(file.calls, vec!["read"; 3]);
+}
+
+#[test]
+fn read_exact_fails_at_eof() {
+    let err = MockFile::with_data(NOT_READY, b"hello")
+        .read_exact([0; 11])
+        .wait()
+        .unwrap_err();
+
+    assert_eq!(err.operation(), "read_exact");
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    assert_eq!(err.file().pos, 5);
+}
+
+#[test]
+fn write_all_writes_everything() {
+    let (file, _, n) = MockFile::new(NOT_READY)
+        .write_all("hello world")
+        .wait()
+        .unwrap();
+
+    assert_eq!(n, 11);
+    assert_eq!(file.data, b"hello world");
+    assert_eq!(file.polls, 3 * (NOT_READY + 1));
+}
+
+#[test]
+fn round_trips_through_tokio_fs() {
+    let path = env::temp_dir().join("file-futures-read-write");
+
+    let future = tokio_fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .map_err(Into::into)
+        .and_then(|file| file.write_all(b"hello world").map_err(Into::into))
+        .and_then(|(file, _, _)| file.seek(SeekFrom::Start(6)).map_err(Into::into))
+        .and_then(|(file, _)| file.read_exact(vec![0; 5]).map_err(Into::into))
+        .map(|(_, buf, _)| buf);
+
+    let buf: Result<_, io::Error> = tokio::runtime::Runtime::new().unwrap().block_on(future);
+    assert_eq!(buf.unwrap(), b"world");
+}
+
+#[test]
+fn positional_io_leaves_the_cursor_alone() {
+    let path = env::temp_dir().join("file-futures-positional");
+
+    let future = tokio_fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .map_err(Into::into)
+        .and_then(|file| file.write_all(b"hello world").map_err(Into::into))
+        .and_then(|(file, _, _)| file.seek(SeekFrom::Start(2)).map_err(Into::into))
+        .and_then(|(file, _)| file.write_at(0, b"J").map_err(Into::into))
+        .and_then(|(file, _, _)| file.read_at(6, vec![0; 5]).map_err(Into::into))
+        .and_then(|(file, world, _)| {
+            file.seek(SeekFrom::Current(0))
+                .map(move |(file, pos)| (file, world, pos))
+                .map_err(Into::into)
+        })
+        .and_then(|(file, world, pos)| {
+            file.read_at(0, vec![0; 5])
+                .map(move |(_, hello, _)| (hello, world, pos))
+                .map_err(Into::into)
+        });
+
+    let res: Result<_, io::Error> = tokio::runtime::Runtime::new().unwrap().block_on(future);
+    let (hello, world, pos) = res.unwrap();
+
+    assert_eq!(hello, b"Jello");
+    assert_eq!(world, b"world");
+    assert_eq!(pos, 2);
+}
+
+fn read_file<F, T>(name: &str, contents: &[u8], f: F) -> Result<T, io::Error>
+where
+    F: FnOnce(tokio_fs::File) -> Box<dyn Future<Item = T, Error = io::Error> + Send>
+        + Send
+        + 'static,
+    T: Send + 'static,
+{
+    let path = env::temp_dir().join(name);
+    std::fs::write(&path, contents).unwrap();
+
+    let future = tokio_fs::File::open(path).and_then(f);
+
+    tokio::runtime::Runtime::new().unwrap().block_on(future)
+}
+
+#[test]
+fn read_to_end_reads_everything() {
+    let contents = vec![7; 20_000];
+
+    let buf = read_file("file-futures-read-to-end", &contents, |file| {
+        Box::new(file.read_to_end(20_000).map(|(_, buf)| buf).map_err(Into::into))
+    });
+
+    assert_eq!(buf.unwrap(), contents);
+}
+
+#[test]
+fn read_to_end_enforces_the_limit() {
+    let err = read_file("file-futures-read-to-end-limit", &[7; 100], |file| {
+        Box::new(file.read_to_end(99).map(|_| ()).map_err(Into::into))
+    })
+    .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    let limit = err.get_ref().unwrap().downcast_ref::<LimitExceeded>().unwrap();
+    assert_eq!(limit.limit(), 99);
+}
+
+#[test]
+fn read_to_end_survives_not_ready() {
+    let (file, buf) = MockFile::with_data(NOT_READY, b"hello world")
+        .read_to_end(u64::MAX)
+        .wait()
+        .unwrap();
+
+    assert_eq!(buf, b"hello world");
+    assert_eq!(file.calls[0], "metadata");
+}
+
+#[test]
+fn read_to_end_stops_reading_past_the_limit() {
+    let err = MockFile::with_data(0, &[7; 2048])
+        .read_to_end(1024)
+        .wait()
+        .unwrap_err();
+
+    assert_eq!(err.operation(), "read_to_end");
+    assert_eq!(err.file().pos, 1025);
+}
+
+#[test]
+fn read_to_end_limits_what_is_left_after_the_cursor() {
+    let (_, buf) = MemFile::with_contents([1; 10])
+        .seek(SeekFrom::Start(8))
+        .and_then(|(file, _)| file.read_to_end(5))
+        .wait()
+        .unwrap();
+
+    assert_eq!(buf, [1; 2]);
+}
+
+#[test]
+fn read_to_string_reads_utf8() {
+    let string = read_file("file-futures-read-to-string", "héllo".as_bytes(), |file| {
+        Box::new(file.read_to_string(64).map(|(_, string)| string).map_err(Into::into))
+    });
+
+    assert_eq!(string.unwrap(), "héllo");
+}
+
+#[test]
+fn read_to_string_rejects_invalid_utf8() {
+    let err = MockFile::with_data(0, &[0xff, 0xfe])
+        .read_to_string(64)
+        .wait()
+        .unwrap_err();
+
+    assert_eq!(err.operation(), "read_to_string");
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
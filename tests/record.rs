@@ -0,0 +1,128 @@
+extern crate file_futures;
+extern crate futures;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+
+use std::{
+    io::{ErrorKind, SeekFrom},
+    thread,
+    time::Duration,
+};
+
+use file_futures::{
+    AsyncFile, Fault, FaultPlan, FaultyFile, FileOp, MemFile, RecordedCall, RecordedValue,
+    RecordingFile, ReplayFile, TraceEvent,
+};
+use futures::{future, Async, Future};
+
+/// Write, read back, truncate, then fail a seek, resolving to what was read and the error.
+fn session<T>(file: T) -> (T, String, ErrorKind)
+where
+    T: AsyncFile,
+{
+    let (file, contents) = file
+        .write_all(b"hello world")
+        .and_then(|(file, _, _)| file.seek(SeekFrom::Start(6)))
+        .and_then(|(file, _)| file.read_to_string(64))
+        .and_then(|(file, contents)| file.set_len(5).map(|file| (file, contents)))
+        .wait()
+        .unwrap();
+
+    let err = file.seek(SeekFrom::Current(-20)).wait().err().unwrap();
+    let kind = err.kind();
+
+    (err.into_file(), contents, kind)
+}
+
+fn record() -> Vec<TraceEvent> {
+    let (file, contents, kind) = session(RecordingFile::new(MemFile::new()));
+
+    assert_eq!(contents, "world");
+    assert_eq!(kind, ErrorKind::InvalidInput);
+    assert_eq!(file.get_ref().contents(), b"hello");
+
+    file.trace()
+}
+
+#[test]
+fn records_calls_and_results() {
+    let trace = record();
+    let calls: Vec<_> = trace.iter().map(|event| event.call.clone()).collect();
+
+    assert_eq!(calls[0], RecordedCall::Write(11));
+    assert_eq!(calls[1], RecordedCall::Seek(SeekFrom::Start(6)));
+    assert_eq!(calls[2], RecordedCall::Metadata);
+    assert_eq!(calls[calls.len() - 2], RecordedCall::SetLen(5));
+
+    assert_eq!(trace[0].result, Ok(RecordedValue::Written(11)));
+    assert_eq!(trace[1].result, Ok(RecordedValue::Position(6)));
+
+    let err = trace.last().unwrap().result.clone().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidInput);
+}
+
+#[test]
+fn abandoned_calls_leave_no_start_time_behind() {
+    let plan = FaultPlan::new(0).on_call(FileOp::Seek, 0, Fault::NotReady);
+    let file = RecordingFile::new(FaultyFile::new(MemFile::new(), plan));
+    let mut seek = file.seek(SeekFrom::Start(1));
+
+    let file = future::lazy(move || {
+        assert!(seek.poll().unwrap().is_not_ready());
+        thread::sleep(Duration::from_millis(20));
+
+        match seek.into_inner().seek(SeekFrom::Start(2)).poll().unwrap() {
+            Async::Ready((file, _)) => Ok::<_, ()>(file),
+            Async::NotReady => panic!("Seek didn't finish"),
+        }
+    })
+    .wait()
+    .unwrap();
+
+    let trace = file.trace();
+    assert_eq!(trace.len(), 1);
+    assert_eq!(trace[0].call, RecordedCall::Seek(SeekFrom::Start(2)));
+    assert!(trace[0].duration < Duration::from_millis(20));
+}
+
+#[test]
+fn replays_a_trace() {
+    let file = ReplayFile::new(record());
+    let (file, contents, kind) = session(file);
+
+    assert_eq!(contents, "world");
+    assert_eq!(kind, ErrorKind::InvalidInput);
+    assert_eq!(file.remaining(), 0);
+}
+
+#[test]
+fn replay_rejects_calls_out_of_order() {
+    let file = ReplayFile::new(record());
+
+    let err = file.set_len(5).wait().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    let (file, _, n) = err.into_file().write(b"hello world").wait().unwrap();
+    assert_eq!(n, 11);
+    assert_eq!(file.remaining(), record().len() - 1);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn traces_round_trip_through_serde() {
+    use file_futures::{Fault, FaultPlan, FaultyFile, FileOp};
+
+    let plan = FaultPlan::new(0).on_call(FileOp::SyncAll, 0, Fault::Eio);
+    let file = RecordingFile::new(FaultyFile::new(MemFile::new(), plan));
+
+    let file = file.sync_all().wait().unwrap_err().into_file();
+    let file = file.metadata().wait().unwrap().0;
+
+    let json = serde_json::to_string(&file.trace()).unwrap();
+    let trace: Vec<TraceEvent> = serde_json::from_str(&json).unwrap();
+    assert_eq!(trace, file.trace());
+
+    let recorded = trace[0].result.clone().unwrap_err();
+    let err = ReplayFile::new(trace).sync_all().wait().unwrap_err();
+    assert_eq!(err.error().raw_os_error(), recorded.raw_os_error);
+}
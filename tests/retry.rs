@@ -0,0 +1,128 @@
+extern crate file_futures;
+extern crate futures;
+
+use std::{
+    io::ErrorKind,
+    time::{Duration, Instant},
+};
+
+use file_futures::{
+    AsyncFile, Fault, FaultPlan, FaultyFile, FileOp, MemFile, RetriesExhausted, RetryPolicy,
+    RetryingFile,
+};
+use futures::{future, Future};
+
+fn quick() -> RetryPolicy {
+    RetryPolicy::default().with_backoff(Duration::from_millis(1), Duration::from_millis(1))
+}
+
+#[test]
+fn transient_errors_are_retried() {
+    let interrupted = Fault::Error(ErrorKind::Interrupted);
+    let plan = FaultPlan::new(0)
+        .on_call(FileOp::SyncAll, 0, interrupted)
+        .on_call(FileOp::SyncAll, 1, Fault::Error(ErrorKind::WouldBlock));
+    let file = RetryingFile::new(FaultyFile::new(MemFile::new(), plan), quick());
+
+    let file = file.sync_all().wait().unwrap();
+    assert_eq!(file.get_ref().calls(FileOp::SyncAll), 3);
+}
+
+#[test]
+fn the_final_error_counts_the_attempts() {
+    let stale = Fault::Error(ErrorKind::StaleNetworkFileHandle);
+    let plan = FaultPlan::new(0).with_probability(FileOp::SetLen, 1.0, stale);
+    let policy = quick().with_max_attempts(4);
+    let file = RetryingFile::new(FaultyFile::new(MemFile::new(), plan), policy);
+
+    let err = file.set_len(5).wait().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::StaleNetworkFileHandle);
+
+    let exhausted = err
+        .error()
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<RetriesExhausted>())
+        .unwrap();
+    assert_eq!(exhausted.attempts(), 4);
+    assert_eq!(exhausted.error().kind(), ErrorKind::StaleNetworkFileHandle);
+
+    assert_eq!(err.into_file().get_ref().calls(FileOp::SetLen), 4);
+}
+
+#[test]
+fn other_errors_come_back_as_they_are() {
+    let plan = FaultPlan::new(0).on_call(FileOp::Seek, 0, Fault::Eio);
+    let file = RetryingFile::new(FaultyFile::new(MemFile::new(), plan), quick());
+
+    let err = file.seek(std::io::SeekFrom::Start(0)).wait().unwrap_err();
+    #[cfg(unix)]
+    assert_eq!(err.error().raw_os_error(), Some(5));
+
+    assert_eq!(err.into_file().get_ref().calls(FileOp::Seek), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn errnos_can_be_retried() {
+    let plan = FaultPlan::new(0).on_call(FileOp::Write, 0, Fault::Enospc);
+    let policy = RetryPolicy::new(2).retry_errno(28);
+    let file = RetryingFile::new(FaultyFile::new(MemFile::new(), plan), policy);
+
+    let (file, _, _) = file.write_all(b"abc").wait().unwrap();
+    assert_eq!(file.get_ref().get_ref().contents(), b"abc");
+}
+
+#[test]
+fn backoff_doubles() {
+    let policy =
+        RetryPolicy::new(5).with_backoff(Duration::from_millis(20), Duration::from_millis(50));
+
+    assert_eq!(policy.backoff(1), Duration::from_millis(20));
+    assert_eq!(policy.backoff(2), Duration::from_millis(40));
+    assert_eq!(policy.backoff(3), Duration::from_millis(50));
+    assert_eq!(policy.backoff(40), Duration::from_millis(50));
+
+    let interrupted = Fault::Error(ErrorKind::Interrupted);
+    let plan = FaultPlan::new(0)
+        .on_call(FileOp::Metadata, 0, interrupted)
+        .on_call(FileOp::Metadata, 1, interrupted);
+    let file = RetryingFile::new(
+        FaultyFile::new(MemFile::new(), plan),
+        policy.retry_kind(ErrorKind::Interrupted),
+    );
+
+    let start = Instant::now();
+    file.metadata().wait().unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(60));
+}
+
+#[test]
+fn try_lock_would_block_is_not_retried() {
+    let file = MemFile::new();
+    let _held = file.reopen().lock_exclusive().wait().unwrap();
+
+    let plan = FaultPlan::new(0);
+    let file = RetryingFile::new(FaultyFile::new(file, plan), quick());
+
+    let err = file.try_lock_exclusive().wait().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::WouldBlock);
+    assert_eq!(err.into_file().get_ref().calls(FileOp::TryLock), 1);
+}
+
+#[test]
+fn abandoned_operations_leave_no_backoff_behind() {
+    let plan = FaultPlan::new(0).on_call(FileOp::SetLen, 0, Fault::Error(ErrorKind::Interrupted));
+    let backoff = Duration::from_secs(60);
+    let policy = RetryPolicy::default().with_backoff(backoff, backoff);
+    let mut set_len = RetryingFile::new(FaultyFile::new(MemFile::new(), plan), policy).set_len(1);
+
+    future::lazy(move || {
+        assert!(set_len.poll().unwrap().is_not_ready());
+
+        let mut set_len = set_len.into_inner().set_len(2);
+        assert!(set_len.poll().unwrap().is_ready());
+        Ok::<_, ()>(())
+    })
+    .wait()
+    .unwrap();
+}
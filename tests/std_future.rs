@@ -0,0 +1,199 @@
+#![cfg(feature = "futures03")]
+
+extern crate file_futures;
+extern crate futures;
+extern crate futures03;
+extern crate tokio;
+extern crate tokio_fs;
+
+mod common;
+
+use std::{
+    env,
+    fs::Permissions,
+    io::{Error, ErrorKind, Result, SeekFrom},
+    task::{Context, Poll},
+};
+
+use common::MockFile;
+use file_futures::{std_future::AsyncFile, FileMetadata, LockMode};
+use futures03::{
+    compat::{Compat, Future01CompatExt},
+    executor::block_on,
+    TryFutureExt,
+};
+use tokio::runtime::Runtime;
+
+/// A file that only implements the `Context`-taking `AsyncFile`, reporting `Pending` once
+/// before each operation completes.
+#[derive(Debug)]
+struct ContextFile {
+    pending: bool,
+    polls: usize,
+    pos: u64,
+    len: u64,
+}
+
+impl ContextFile {
+    fn new() -> Self {
+        ContextFile {
+            pending: false,
+            polls: 0,
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    fn poll_op<T, F>(&mut self, cx: &mut Context, f: F) -> Poll<Result<T>>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.polls += 1;
+        self.pending = !self.pending;
+
+        if self.pending {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+
+        Poll::Ready(f(self))
+    }
+}
+
+fn unsupported<T>() -> Result<T> {
+    Err(Error::other("unsupported"))
+}
+
+impl AsyncFile for ContextFile {
+    type Cloned = tokio_fs::File;
+
+    fn poll_seek(&mut self, cx: &mut Context, pos: SeekFrom) -> Poll<Result<u64>> {
+        self.poll_op(cx, |file| match pos {
+            SeekFrom::Start(pos) => {
+                file.pos = pos;
+                Ok(pos)
+            }
+            _ => unsupported(),
+        })
+    }
+
+    fn poll_sync_all(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        self.poll_op(cx, |_| Ok(()))
+    }
+
+    fn poll_sync_data(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        self.poll_op(cx, |_| Ok(()))
+    }
+
+    fn poll_set_len(&mut self, cx: &mut Context, size: u64) -> Poll<Result<()>> {
+        self.poll_op(cx, |file| {
+            file.len = size;
+            Ok(())
+        })
+    }
+
+    fn poll_metadata(&mut self, cx: &mut Context) -> Poll<Result<FileMetadata>> {
+        self.poll_op(cx, |_| unsupported())
+    }
+
+    fn poll_try_clone(&mut self, cx: &mut Context) -> Poll<Result<Self::Cloned>> {
+        self.poll_op(cx, |_| unsupported())
+    }
+
+    fn poll_set_permissions(&mut self, cx: &mut Context, _: Permissions) -> Poll<Result<()>> {
+        self.poll_op(cx, |_| unsupported())
+    }
+
+    fn poll_read(&mut self, cx: &mut Context, _: &mut [u8]) -> Poll<Result<usize>> {
+        self.poll_op(cx, |_| Ok(0))
+    }
+
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<Result<usize>> {
+        self.poll_op(cx, |_| Ok(buf.len()))
+    }
+
+    fn poll_read_at(&mut self, cx: &mut Context, _: &mut [u8], _: u64) -> Poll<Result<usize>> {
+        self.poll_op(cx, |_| Ok(0))
+    }
+
+    fn poll_write_at(&mut self, cx: &mut Context, buf: &[u8], _: u64) -> Poll<Result<usize>> {
+        self.poll_op(cx, |_| Ok(buf.len()))
+    }
+
+    fn poll_lock(&mut self, cx: &mut Context, _: LockMode) -> Poll<Result<()>> {
+        self.poll_op(cx, |_| Ok(()))
+    }
+
+    fn poll_try_lock(&mut self, cx: &mut Context, _: LockMode) -> Poll<Result<()>> {
+        self.poll_op(cx, |_| Ok(()))
+    }
+
+    fn poll_unlock(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        self.poll_op(cx, |_| Ok(()))
+    }
+}
+
+#[test]
+fn futures01_files_survive_not_ready() {
+    let (file, pos) = block_on(MockFile::new(3).seek(SeekFrom::Start(4))).unwrap();
+
+    assert_eq!(pos, 4);
+    assert_eq!(file.polls, 4);
+
+    let file = block_on(file.set_len(8).and_then(|file| file.sync_data())).unwrap();
+
+    assert_eq!(file.calls, vec!["seek", "set_len", "sync_data"]);
+}
+
+#[test]
+fn errors_return_the_file() {
+    let err = block_on(MockFile::failing(1).set_len(8)).unwrap_err();
+
+    assert_eq!(err.operation(), "set_len");
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    assert_eq!(err.into_file().calls, vec!["set_len"]);
+}
+
+#[test]
+fn context_files_drive_every_future() {
+    let (file, pos) = block_on(ContextFile::new().seek(SeekFrom::Start(7))).unwrap();
+    assert_eq!((pos, file.pos, file.polls), (7, 7, 2));
+
+    let file = block_on(file.set_len(30)).unwrap();
+    assert_eq!(file.len, 30);
+
+    let (file, _, n) = block_on(file.write_all(vec![1; 5])).unwrap();
+    assert_eq!(n, 5);
+
+    let locked = block_on(file.lock_exclusive()).unwrap();
+    assert_eq!(locked.mode(), LockMode::Exclusive);
+
+    let err = block_on(ContextFile::new().metadata()).unwrap_err();
+    assert_eq!(err.operation(), "metadata");
+    assert_eq!(err.into_file().polls, 2);
+}
+
+#[test]
+fn tokio_fs_round_trip() {
+    let path = env::temp_dir().join("file-futures-std-future");
+    let open = tokio_fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path);
+
+    let future = open
+        .compat()
+        .map_err(Error::from)
+        .and_then(|file| {
+            file.write_at(2, b"hello")
+                .and_then(|(file, _, _)| file.read_at(0, vec![0; 7]))
+                .map_err(Error::from)
+        });
+
+    let (_, buf, n) = Runtime::new().unwrap().block_on(Compat::new(future)).unwrap();
+
+    assert_eq!(n, 7);
+    assert_eq!(buf, b"\0\0hello");
+}
@@ -0,0 +1,60 @@
+extern crate file_futures;
+extern crate futures;
+extern crate tokio;
+extern crate tokio_fs;
+
+mod common;
+
+use std::env;
+
+use common::MockFile;
+use file_futures::{AsyncFile, SyncPolicy};
+use futures::Future;
+
+#[test]
+fn sync_data_calls_sync_data() {
+    let file = MockFile::new(1).sync_data().wait().unwrap();
+
+    assert_eq!(file.calls, vec!["sync_data"]);
+}
+
+#[test]
+fn sync_with_none_does_nothing() {
+    let file = MockFile::new(1).sync_with(SyncPolicy::None).wait().unwrap();
+
+    assert!(file.calls.is_empty());
+}
+
+#[test]
+fn sync_with_follows_policy() {
+    let file = MockFile::new(1).sync_with(SyncPolicy::Data).wait().unwrap();
+    assert_eq!(file.calls, vec!["sync_data"]);
+
+    let file = MockFile::new(1).sync_with(SyncPolicy::All).wait().unwrap();
+    assert_eq!(file.calls, vec!["sync_all"]);
+}
+
+#[test]
+fn sync_with_syncs_directory() {
+    let policy = SyncPolicy::DataThenDirectory(env::temp_dir());
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let file = runtime
+        .block_on(MockFile::new(1).sync_with(policy))
+        .unwrap();
+
+    assert_eq!(file.calls, vec!["sync_data"]);
+}
+
+#[test]
+fn sync_with_reports_directory_errors() {
+    let policy = SyncPolicy::DataThenDirectory(env::temp_dir().join("file-futures-missing-dir"));
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let err = runtime
+        .block_on(MockFile::new(1).sync_with(policy))
+        .unwrap_err();
+
+    assert_eq!(err.operation(), "open_directory");
+    assert_eq!(err.file().calls, vec!["sync_data"]);
+}
@@ -0,0 +1,86 @@
+extern crate file_futures;
+extern crate futures;
+extern crate tokio_fs;
+
+mod common;
+
+use std::{
+    io::{ErrorKind, SeekFrom},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::MockFile;
+use file_futures::{AsyncFile, Fault, FaultPlan, FaultyFile, FileOp, MemFile};
+use futures::{future, Future};
+
+#[test]
+fn stuck_operations_time_out() {
+    let plan = FaultPlan::new(0).on_call(FileOp::SyncAll, 0, Fault::Delay(Duration::from_secs(60)));
+    let file = FaultyFile::new(MemFile::with_contents("abc"), plan);
+
+    let start = Instant::now();
+    let err = file
+        .with_timeout(Duration::from_millis(50))
+        .sync_all()
+        .wait()
+        .unwrap_err();
+
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    assert_eq!(err.operation(), "sync_all");
+
+    let file = err.into_file().set_len(1).wait().unwrap();
+    assert_eq!(file.get_ref().get_ref().contents(), b"a");
+}
+
+#[test]
+fn each_call_gets_the_whole_timeout() {
+    let delay = Fault::Delay(Duration::from_millis(40));
+    let plan = FaultPlan::new(0)
+        .on_call(FileOp::Read, 0, delay)
+        .on_call(FileOp::Read, 1, delay);
+    let file = FaultyFile::new(MemFile::with_contents("abc"), plan);
+
+    let start = Instant::now();
+    let (file, contents) = file
+        .with_timeout(Duration::from_millis(60))
+        .read_to_end(64)
+        .wait()
+        .unwrap();
+
+    assert!(start.elapsed() >= Duration::from_millis(80));
+    assert_eq!(contents, b"abc");
+    assert_eq!(file.get_ref().calls(FileOp::Read), 2);
+}
+
+#[test]
+fn clones_keep_the_timeout() {
+    let (file, clone) = MemFile::new()
+        .with_timeout(Duration::from_secs(1))
+        .try_clone()
+        .wait()
+        .unwrap();
+
+    assert_eq!(clone.timeout(), file.timeout());
+}
+
+#[test]
+fn abandoned_operations_leave_no_deadline_behind() {
+    let mut seek = MockFile::new(1)
+        .with_timeout(Duration::from_millis(20))
+        .seek(SeekFrom::Start(0));
+
+    let seek = future::lazy(move || {
+        assert!(seek.poll().unwrap().is_not_ready());
+        Ok::<_, ()>(seek)
+    })
+    .wait()
+    .unwrap();
+
+    thread::sleep(Duration::from_millis(40));
+
+    let (_, pos) = seek.into_inner().seek(SeekFrom::Start(3)).wait().unwrap();
+    assert_eq!(pos, 3);
+}
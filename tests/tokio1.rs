@@ -0,0 +1,98 @@
+#![cfg(feature = "tokio1")]
+
+extern crate file_futures;
+extern crate futures;
+extern crate futures03;
+extern crate tokio1;
+
+use std::{
+    convert::TryFrom,
+    env,
+    fs::{self, OpenOptions},
+    io::SeekFrom,
+};
+
+use file_futures::{std_future::AsyncFile, tokio1::File};
+use futures03::{executor::block_on, TryFutureExt};
+use tokio1::runtime::{Builder, Runtime};
+
+fn runtime() -> Runtime {
+    Builder::new_current_thread().build().unwrap()
+}
+
+fn open(rt: &Runtime, name: &str) -> File {
+    let std = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(env::temp_dir().join(name))
+        .unwrap();
+
+    File::with_handle(std, rt.handle().clone())
+}
+
+#[test]
+fn round_trip() {
+    let rt = runtime();
+    let file = open(&rt, "file-futures-tokio1-round-trip");
+
+    let future = file
+        .write_all(b"hello world")
+        .and_then(|(file, _, _)| file.seek(SeekFrom::Start(6)))
+        .and_then(|(file, _)| file.read_exact(vec![0; 5]))
+        .and_then(|(file, buf, _)| file.set_len(5).map_ok(|file| (file, buf)))
+        .and_then(|(file, buf)| file.try_clone().map_ok(|(_, clone)| (clone, buf)))
+        .and_then(|(clone, buf)| clone.metadata().map_ok(|(_, metadata)| (metadata, buf)));
+
+    let (metadata, buf) = rt.block_on(future).unwrap();
+
+    assert_eq!(buf, b"world");
+    assert_eq!(metadata.len(), 5);
+}
+
+#[test]
+fn polls_from_any_executor() {
+    let rt = runtime();
+    let file = open(&rt, "file-futures-tokio1-executor");
+
+    let (file, _, _) = block_on(file.write_at(3, b"abc")).unwrap();
+
+    // The futures 0.1 trait works too, through the same file.
+    let read = file_futures::AsyncFile::read_at(file, 0, vec![1; 6]);
+    let (_, buf, n) = futures::Future::wait(read).unwrap();
+
+    assert_eq!(n, 6);
+    assert_eq!(buf, b"\0\0\0abc");
+}
+
+#[test]
+fn errors_return_the_file() {
+    let rt = runtime();
+    let path = env::temp_dir().join("file-futures-tokio1-errors");
+    fs::write(&path, "contents").unwrap();
+    let file = File::with_handle(fs::File::open(&path).unwrap(), rt.handle().clone());
+
+    let err = rt.block_on(file.set_len(0)).unwrap_err();
+    assert_eq!(err.operation(), "set_len");
+
+    let (_, contents) = rt.block_on(err.into_file().read_to_string(64)).unwrap();
+    assert_eq!(contents, "contents");
+}
+
+#[test]
+fn converts_from_tokio_files() {
+    let rt = runtime();
+    let path = env::temp_dir().join("file-futures-tokio1-from-tokio");
+    fs::write(&path, "contents").unwrap();
+
+    let tokio = rt.block_on(tokio1::fs::File::open(&path)).unwrap();
+    let file = rt.block_on(File::from_tokio(tokio));
+
+    let (_, contents) = rt.block_on(file.read_to_string(64)).unwrap();
+    assert_eq!(contents, "contents");
+
+    let tokio = rt.block_on(tokio1::fs::File::open(&path)).unwrap();
+    let _guard = rt.enter();
+    assert!(File::try_from(tokio).is_ok());
+}
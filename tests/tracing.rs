@@ -0,0 +1,216 @@
+#![cfg(feature = "tracing")]
+
+extern crate file_futures;
+extern crate futures;
+extern crate tokio;
+extern crate tracing;
+
+use std::{
+    collections::HashMap,
+    env, fmt,
+    io::{self, SeekFrom},
+    sync::{Arc, Mutex},
+};
+
+use file_futures::{AsyncFile, MemFile, NamedFile, SyncPolicy};
+use futures::{future, Future};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Dispatch, Event, Metadata, Subscriber,
+};
+
+/// What a `Recorder` saw of one span: its fields, and the messages of events inside it.
+#[derive(Clone, Debug, Default)]
+struct SpanRecord {
+    fields: HashMap<String, String>,
+    events: Vec<String>,
+}
+
+/// A subscriber that keeps every span and event, so tests can look at them afterwards.
+#[derive(Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<SpanRecord>>>,
+    current: Arc<Mutex<Vec<usize>>>,
+}
+
+struct Fields<'a>(&'a mut HashMap<String, String>);
+
+impl<'a> Visit for Fields<'a> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.to_owned());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{:?}", value));
+    }
+}
+
+impl Recorder {
+    fn spans(&self) -> Vec<SpanRecord> {
+        self.spans.lock().unwrap().clone()
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes) -> Id {
+        let mut span = SpanRecord::default();
+        attrs.record(&mut Fields(&mut span.fields));
+
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span);
+
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, id: &Id, values: &Record) {
+        let mut spans = self.spans.lock().unwrap();
+        let span = &mut spans[id.into_u64() as usize - 1];
+
+        values.record(&mut Fields(&mut span.fields));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event) {
+        let mut fields = HashMap::new();
+        event.record(&mut Fields(&mut fields));
+
+        if let Some(&index) = self.current.lock().unwrap().last() {
+            let message = fields.remove("message").unwrap_or_default();
+            let event = match fields.remove("error") {
+                Some(error) => format!("{}: {}", message, error),
+                None => message,
+            };
+
+            self.spans.lock().unwrap()[index].events.push(event);
+        }
+    }
+
+    fn enter(&self, id: &Id) {
+        let index = id.into_u64() as usize - 1;
+        self.current.lock().unwrap().push(index);
+    }
+
+    fn exit(&self, _: &Id) {
+        self.current.lock().unwrap().pop();
+    }
+}
+
+fn record<F>(f: F) -> Vec<SpanRecord>
+where
+    F: FnOnce(),
+{
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), f);
+    recorder.spans()
+}
+
+/// Like `record`, for a future that needs a tokio runtime, polling it wherever the runtime does.
+fn record_on_runtime<F>(mut future: F) -> Vec<SpanRecord>
+where
+    F: Future<Error = io::Error> + Send + 'static,
+    F::Item: Send + 'static,
+{
+    let recorder = Recorder::default();
+    let dispatch = Dispatch::new(recorder.clone());
+
+    let future = future::poll_fn(move || {
+        tracing::dispatcher::with_default(&dispatch, || future.poll())
+    });
+
+    tokio::runtime::Runtime::new().unwrap().block_on(future).unwrap();
+    recorder.spans()
+}
+
+#[test]
+fn spans_name_the_operation_and_outcome() {
+    let spans = record(|| {
+        MemFile::with_contents("hello")
+            .set_len(30)
+            .and_then(|file| file.seek(SeekFrom::End(0)))
+            .wait()
+            .unwrap();
+    });
+
+    assert_eq!(spans.len(), 2);
+
+    assert_eq!(spans[0].fields["op"], "set_len");
+    assert_eq!(spans[0].fields["args"], "30");
+    assert_eq!(spans[0].fields["outcome"], "ready");
+    assert_eq!(spans[0].events, ["ready"]);
+
+    assert_eq!(spans[1].fields["op"], "seek");
+    assert_eq!(spans[1].fields["args"], "End(0)");
+    assert!(!spans[1].fields.contains_key("path"));
+}
+
+#[test]
+fn failures_are_recorded_with_the_error() {
+    let spans = record(|| {
+        MemFile::new()
+            .seek(SeekFrom::Current(-1))
+            .wait()
+            .err()
+            .unwrap();
+    });
+
+    assert_eq!(spans[0].fields["outcome"], "error");
+    assert_eq!(spans[0].events.len(), 1);
+    assert!(spans[0].events[0].starts_with("failed: "));
+}
+
+#[test]
+fn one_span_covers_every_poll() {
+    let spans = record(|| {
+        MemFile::with_contents("abc")
+            .read_to_string(64)
+            .wait()
+            .unwrap();
+    });
+
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].fields["op"], "read_to_string");
+    assert_eq!(spans[0].fields["args"], "limit 64");
+}
+
+#[test]
+fn spans_name_the_file() {
+    let spans = record(|| {
+        NamedFile::new(MemFile::new(), "/var/data/x")
+            .sync_all()
+            .wait()
+            .unwrap();
+    });
+
+    assert_eq!(spans[0].fields["path"], "/var/data/x");
+}
+
+#[test]
+fn path_based_futures_name_their_path() {
+    let path = env::temp_dir().join("file-futures-tracing-atomic");
+    let spans = record_on_runtime(file_futures::atomic_write(
+        path.clone(),
+        "contents",
+        SyncPolicy::None,
+    ));
+
+    assert_eq!(spans[0].fields["op"], "atomic_write");
+    assert_eq!(spans[0].fields["path"], path.display().to_string());
+
+    // The write goes to the temporary file, which is named after the target.
+    let write = spans.iter().find(|span| span.fields["op"] == "write_all").unwrap();
+    assert!(write.fields["path"].contains("file-futures-tracing-atomic."));
+    assert!(write.fields["path"].ends_with(".tmp"));
+
+    let spans = record_on_runtime(file_futures::read(path.clone()));
+
+    assert_eq!(spans[0].fields["op"], "read_file");
+    assert_eq!(spans[0].fields["path"], path.display().to_string());
+    assert_eq!(spans[1].fields["op"], "read_to_end");
+}